@@ -0,0 +1,531 @@
+extern crate crypto;
+extern crate egg_mode;
+extern crate rand;
+extern crate rustc_serialize;
+extern crate time;
+
+use crypto::hmac::Hmac;
+use crypto::mac::Mac;
+use crypto::sha1::Sha1;
+use hyper;
+use hyper::Client as HttpClient;
+use hyper::header::{Authorization, ContentType};
+use hyper::net::HttpsConnector;
+use hyper_native_tls::NativeTlsClient;
+use rand::{OsRng, Rng};
+use rustc_serialize::base64::{STANDARD, ToBase64};
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json;
+use std::collections::BTreeMap;
+use std::env;
+use std::io;
+use std::io::prelude::*;
+use std::str;
+use std::sync::Mutex;
+
+// DEFAULT_REST_BASE_URL is base url of twitter REST API
+pub const DEFAULT_REST_BASE_URL: &'static str = "https://api.twitter.com/1.1";
+// DEFAULT_STREAM_BASE_URL is base url of twitter streaming API
+pub const DEFAULT_STREAM_BASE_URL: &'static str = "https://stream.twitter.com/1.1";
+// DEFAULT_UPLOAD_BASE_URL is base url of twitter media upload API
+pub const DEFAULT_UPLOAD_BASE_URL: &'static str = "https://upload.twitter.com/1.1";
+
+// ENV_CONSUMER_KEY overrides the consumer key
+pub const ENV_CONSUMER_KEY: &'static str = "RUSTYTWIT_CONSUMER_KEY";
+// ENV_CONSUMER_SECRET overrides the consumer key secret
+pub const ENV_CONSUMER_SECRET: &'static str = "RUSTYTWIT_CONSUMER_SECRET";
+// ENV_REST_BASE_URL overrides base url of REST API
+pub const ENV_REST_BASE_URL: &'static str = "RUSTYTWIT_REST_BASE_URL";
+// ENV_STREAM_BASE_URL overrides base url of streaming API
+pub const ENV_STREAM_BASE_URL: &'static str = "RUSTYTWIT_STREAM_BASE_URL";
+// ENV_UPLOAD_BASE_URL overrides base url of media upload API
+pub const ENV_UPLOAD_BASE_URL: &'static str = "RUSTYTWIT_UPLOAD_BASE_URL";
+
+// ApiError
+#[derive(Debug)]
+pub enum ApiError {
+    Io(io::Error),
+    Hyper(hyper::Error),
+    Json(json::ParserError),
+    Status(u16, String),
+    InvalidResponse(String),
+}
+
+impl From<io::Error> for ApiError {
+    fn from(err: io::Error) -> ApiError {
+        ApiError::Io(err)
+    }
+}
+
+impl From<hyper::Error> for ApiError {
+    fn from(err: hyper::Error) -> ApiError {
+        ApiError::Hyper(err)
+    }
+}
+
+impl From<json::ParserError> for ApiError {
+    fn from(err: json::ParserError) -> ApiError {
+        ApiError::Json(err)
+    }
+}
+
+impl From<String> for ApiError {
+    fn from(err: String) -> ApiError {
+        ApiError::InvalidResponse(err)
+    }
+}
+
+// Client sends OAuth signed requests to the configured REST API.
+// egg-mode requests fixed twitter.com endpoints, so requests are signed here to honour the base url override
+#[derive(Clone, Debug)]
+pub struct Client {
+    pub base_url: String,
+    pub upload_base_url: String,
+    consumer_key: String,
+    consumer_secret: String,
+    access_key: String,
+    access_secret: String,
+    // bearer is the token of application-only authentication, requests are not signed with it
+    bearer: Option<String>,
+}
+
+impl Client {
+    pub fn new(config: &::config::Config, token: &egg_mode::Token) -> Client {
+        let mut client = Client::consumer(config);
+        match *token {
+            egg_mode::Token::Access {
+                ref consumer,
+                ref access,
+            } => {
+                client.consumer_key = consumer.key.to_string();
+                client.consumer_secret = consumer.secret.to_string();
+                client.access_key = access.key.to_string();
+                client.access_secret = access.secret.to_string();
+            },
+            egg_mode::Token::Bearer(ref bearer) => client.bearer = Some(bearer.to_string()),
+        }
+        client
+    }
+
+    // consumer creates Client signed by the consumer key pair of config only, PIN authorization requests tokens by it
+    pub fn consumer(config: &::config::Config) -> Client {
+        let (consumer_key, consumer_secret) = config.consumer();
+        Client {
+            base_url: config.rest_base_url(),
+            upload_base_url: config.upload_base_url(),
+            consumer_key: consumer_key,
+            consumer_secret: consumer_secret,
+            access_key: String::new(),
+            access_secret: String::new(),
+            bearer: None,
+        }
+    }
+
+    // request_token requests the temporary token of PIN authorization
+    pub fn request_token(&self) -> Result<egg_mode::KeyPair<'static>, ApiError> {
+        let mut oauth = BTreeMap::new();
+        oauth.insert("oauth_callback".to_owned(), "oob".to_owned());
+        let body = try!(self.post_oauth("request_token", &oauth));
+        let (key, secret) = try!(token_pair(&body));
+        Ok(egg_mode::KeyPair::new(key, secret))
+    }
+
+    // authorize_url returns the url where the user authorizes request and gets the PIN
+    pub fn authorize_url(&self, request: &egg_mode::KeyPair) -> String {
+        format!("{}?oauth_token={}", self.oauth_url("authorize"), percent_encode(&request.key))
+    }
+
+    // access_token exchanges request and the PIN for the access token, and returns it with the screen name
+    pub fn access_token(
+        &self,
+        request: &egg_mode::KeyPair,
+        pin: &str,
+    ) -> Result<(egg_mode::Token<'static>, String), ApiError> {
+        let client = Client {
+            access_key: request.key.to_string(),
+            access_secret: request.secret.to_string(),
+            ..self.clone()
+        };
+        let mut oauth = BTreeMap::new();
+        oauth.insert("oauth_verifier".to_owned(), pin.to_owned());
+        let body = try!(client.post_oauth("access_token", &oauth));
+        let (key, secret) = try!(token_pair(&body));
+        let token = egg_mode::Token::Access {
+            consumer: egg_mode::KeyPair::new(self.consumer_key.clone(), self.consumer_secret.clone()),
+            access: egg_mode::KeyPair::new(key, secret),
+        };
+        Ok((token, body.get("screen_name").cloned().unwrap_or(String::new())))
+    }
+
+    // oauth_url returns the url of the OAuth endpoint
+    fn oauth_url(&self, path: &str) -> String {
+        format!("{}/oauth/{}", oauth_base_url(&self.base_url), path)
+    }
+
+    // post_oauth posts to the OAuth endpoint with oauth parameters and returns the form encoded response
+    fn post_oauth(&self, path: &str, oauth: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>, ApiError> {
+        let url = self.oauth_url(path);
+        let header = try!(self.sign("POST", &url, &BTreeMap::new(), oauth));
+
+        let client = try!(http_client());
+        let mut resp = try!(client.post(&url).header(Authorization(header)).send());
+        let mut body = String::new();
+        try!(resp.read_to_string(&mut body));
+        if !resp.status.is_success() {
+            return Err(ApiError::Status(resp.status.to_u16(), body));
+        }
+        Ok(decode_form(&body))
+    }
+
+    pub fn get(&self, path: &str, params: &BTreeMap<String, String>) -> Result<json::Json, ApiError> {
+        self.get_url(&format!("{}/{}", self.base_url, path), params)
+    }
+
+    pub fn post(&self, path: &str, params: &BTreeMap<String, String>) -> Result<json::Json, ApiError> {
+        self.post_url(&format!("{}/{}", self.base_url, path), params)
+    }
+
+    // upload_url returns the url of path in media upload API
+    pub fn upload_url(&self, path: &str) -> String {
+        format!("{}/{}", self.upload_base_url, path)
+    }
+
+    pub fn get_url(&self, url: &str, params: &BTreeMap<String, String>) -> Result<json::Json, ApiError> {
+        let full_url = match params.is_empty() {
+            true => url.to_owned(),
+            false => format!("{}?{}", url, encode_params(params)),
+        };
+        let header = try!(self.authorization("GET", url, params));
+
+        let client = try!(http_client());
+        let resp = try!(client.get(&full_url).header(Authorization(header)).send());
+        read_response(resp)
+    }
+
+    pub fn post_url(&self, url: &str, params: &BTreeMap<String, String>) -> Result<json::Json, ApiError> {
+        let body = encode_params(params);
+        let header = try!(self.authorization("POST", url, params));
+
+        let client = try!(http_client());
+        let resp = try!(
+            client
+                .post(url)
+                .header(Authorization(header))
+                .header(ContentType::form_url_encoded())
+                .body(body.as_str())
+                .send()
+        );
+        read_response(resp)
+    }
+
+    // post_json posts json body to url, the body is not signed
+    pub fn post_json(&self, url: &str, body: &json::Json) -> Result<json::Json, ApiError> {
+        let body = body.to_string();
+        let header = try!(self.authorization("POST", url, &BTreeMap::new()));
+
+        let client = try!(http_client());
+        let resp = try!(
+            client
+                .post(url)
+                .header(Authorization(header))
+                .header(ContentType::json())
+                .body(body.as_str())
+                .send()
+        );
+        read_response(resp)
+    }
+
+    // authorization returns OAuth 1.0a authorization header value signed by HMAC-SHA1,
+    // or the bearer token of application-only authentication
+    pub fn authorization(
+        &self,
+        method: &str,
+        url: &str,
+        params: &BTreeMap<String, String>,
+    ) -> Result<String, ApiError> {
+        if let Some(ref bearer) = self.bearer {
+            return Ok(format!("Bearer {}", bearer));
+        }
+        self.sign(method, url, params, &BTreeMap::new())
+    }
+
+    // sign returns the OAuth 1.0a authorization header value with extra oauth parameters
+    fn sign(
+        &self,
+        method: &str,
+        url: &str,
+        params: &BTreeMap<String, String>,
+        extra: &BTreeMap<String, String>,
+    ) -> Result<String, ApiError> {
+        let mut oauth = extra.clone();
+        oauth.insert("oauth_consumer_key".to_owned(), self.consumer_key.clone());
+        oauth.insert("oauth_nonce".to_owned(), try!(nonce()));
+        oauth.insert("oauth_signature_method".to_owned(), "HMAC-SHA1".to_owned());
+        oauth.insert("oauth_timestamp".to_owned(), format!("{}", time::now_utc().to_timespec().sec));
+        oauth.insert("oauth_version".to_owned(), "1.0".to_owned());
+        if !self.access_key.is_empty() {
+            oauth.insert("oauth_token".to_owned(), self.access_key.clone());
+        }
+
+        let mut signed = oauth.clone();
+        for (key, value) in params {
+            signed.insert(key.clone(), value.clone());
+        }
+        let signature = signature(method, url, &signed, &self.consumer_secret, &self.access_secret);
+        oauth.insert("oauth_signature".to_owned(), signature);
+
+        let header = oauth
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", percent_encode(key), percent_encode(value)))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok(format!("OAuth {}", header))
+    }
+}
+
+// signature returns the HMAC-SHA1 signature of the request with params including oauth parameters
+fn signature(
+    method: &str,
+    url: &str,
+    params: &BTreeMap<String, String>,
+    consumer_secret: &str,
+    access_secret: &str,
+) -> String {
+    let base = format!("{}&{}&{}", method, percent_encode(url), percent_encode(&encode_params(params)));
+    let key = format!("{}&{}", percent_encode(consumer_secret), percent_encode(access_secret));
+
+    let mut hmac = Hmac::new(Sha1::new(), key.as_bytes());
+    hmac.input(base.as_bytes());
+    hmac.result().code().to_base64(STANDARD)
+}
+
+// client creates Client of the shared token
+pub fn client(config: &::config::Config, token: &Mutex<egg_mode::Token<'static>>) -> Client {
+    match token.lock() {
+        Ok(guard) => Client::new(config, &guard),
+        Err(poisoned) => Client::new(config, &poisoned.into_inner()),
+    }
+}
+
+// verify_credentials returns the authenticated user, it fails with status 401 when the token is revoked
+pub fn verify_credentials(client: &Client) -> Result<json::Json, ApiError> {
+    client.get("account/verify_credentials.json", &BTreeMap::new())
+}
+
+// rest_base_url returns base url of REST API, environment variable takes precedence over config
+pub fn rest_base_url(configured: Option<String>) -> String {
+    let url = env::var(ENV_REST_BASE_URL).ok().or(configured).unwrap_or(
+        DEFAULT_REST_BASE_URL.to_owned(),
+    );
+    url.trim_right_matches('/').to_owned()
+}
+
+// stream_base_url returns base url of streaming API, environment variable takes precedence over config
+pub fn stream_base_url(configured: Option<String>) -> String {
+    let url = env::var(ENV_STREAM_BASE_URL).ok().or(configured).unwrap_or(
+        DEFAULT_STREAM_BASE_URL.to_owned(),
+    );
+    url.trim_right_matches('/').to_owned()
+}
+
+// upload_base_url returns base url of media upload API, environment variable takes precedence over config
+pub fn upload_base_url(configured: Option<String>) -> String {
+    let url = env::var(ENV_UPLOAD_BASE_URL).ok().or(configured).unwrap_or(
+        DEFAULT_UPLOAD_BASE_URL.to_owned(),
+    );
+    url.trim_right_matches('/').to_owned()
+}
+
+pub fn encode_params(params: &BTreeMap<String, String>) -> String {
+    params
+        .iter()
+        .map(|(key, value)| format!("{}={}", percent_encode(key), percent_encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+// percent_encode encodes string as RFC 3986 which OAuth signature requires
+pub fn percent_encode(input: &str) -> String {
+    let mut encoded = String::new();
+    for byte in input.bytes() {
+        match byte {
+            b'a'...b'z' | b'A'...b'Z' | b'0'...b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+// oauth_base_url returns base_url without the version path like /1.1, OAuth endpoints are at the root of the host
+fn oauth_base_url(base_url: &str) -> &str {
+    let host = base_url.find("://").map(|index| index + 3).unwrap_or(0);
+    match base_url.rfind('/') {
+        Some(index) if index > host && base_url[index + 1..].chars().all(|c| c.is_digit(10) || c == '.') => {
+            &base_url[..index]
+        },
+        _ => base_url,
+    }
+}
+
+// decode_form decodes the form encoded response of OAuth endpoints
+fn decode_form(body: &str) -> BTreeMap<String, String> {
+    body.trim()
+        .split('&')
+        .filter_map(|pair| {
+            let mut pair = pair.splitn(2, '=');
+            match (pair.next(), pair.next()) {
+                (Some(key), Some(value)) => Some((percent_decode(key), percent_decode(value))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+// percent_decode decodes string encoded by percent_encode or as a form, an invalid escape is kept as it is
+fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = match bytes[index] {
+            b'%' if index + 2 < bytes.len() => {
+                str::from_utf8(&bytes[index + 1..index + 3]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            },
+            _ => None,
+        };
+        match (escaped, bytes[index]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                index += 3;
+            },
+            (None, b'+') => {
+                decoded.push(b' ');
+                index += 1;
+            },
+            (None, byte) => {
+                decoded.push(byte);
+                index += 1;
+            },
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// token_pair returns oauth_token and oauth_token_secret of the response of OAuth endpoints
+fn token_pair(body: &BTreeMap<String, String>) -> Result<(String, String), ApiError> {
+    match (body.get("oauth_token"), body.get("oauth_token_secret")) {
+        (Some(key), Some(secret)) => Ok((key.clone(), secret.clone())),
+        _ => Err(ApiError::InvalidResponse(format!("no token in {:?}", body))),
+    }
+}
+
+fn nonce() -> Result<String, ApiError> {
+    let mut bytes = [0u8; 16];
+    try!(OsRng::new()).fill_bytes(&mut bytes);
+    Ok(bytes.to_hex())
+}
+
+fn http_client() -> Result<HttpClient, ApiError> {
+    let ssl = match NativeTlsClient::new() {
+        Ok(ssl) => ssl,
+        Err(err) => return Err(ApiError::InvalidResponse(format!("{:?}", err))),
+    };
+    let connector = HttpsConnector::new(ssl);
+    Ok(HttpClient::with_connector(connector))
+}
+
+fn read_response(mut resp: hyper::client::Response) -> Result<json::Json, ApiError> {
+    let mut body = String::new();
+    try!(resp.read_to_string(&mut body));
+    if !resp.status.is_success() {
+        return Err(ApiError::Status(resp.status.to_u16(), body));
+    }
+    // APPEND of media upload responds no content
+    if body.trim().is_empty() {
+        return Ok(json::Json::Null);
+    }
+    Ok(try!(json::Json::from_str(&body)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn percent_encode_reserved() {
+        assert_eq!(percent_encode("a b&c=d/~"), "a%20b%26c%3Dd%2F~");
+        assert_eq!(percent_encode("Az09-._~"), "Az09-._~");
+        assert_eq!(percent_encode("+*!"), "%2B%2A%21");
+    }
+
+    #[test]
+    fn percent_encode_utf8() {
+        assert_eq!(percent_encode("é"), "%C3%A9");
+    }
+
+    #[test]
+    fn encode_params_sorted() {
+        let mut params = BTreeMap::new();
+        params.insert("status".to_owned(), "hello world!".to_owned());
+        params.insert("count".to_owned(), "20".to_owned());
+        assert_eq!(encode_params(&params), "count=20&status=hello%20world%21");
+    }
+
+    #[test]
+    fn encode_params_empty() {
+        assert_eq!(encode_params(&BTreeMap::new()), "");
+    }
+
+    #[test]
+    fn signature_of_twitter_example() {
+        let mut params = BTreeMap::new();
+        params.insert("status".to_owned(), "Hello Ladies + Gentlemen, a signed OAuth request!".to_owned());
+        params.insert("include_entities".to_owned(), "true".to_owned());
+        params.insert("oauth_consumer_key".to_owned(), "xvz1evFS4wEEPTGEFPHBog".to_owned());
+        params.insert("oauth_nonce".to_owned(), "kYjzVBB8Y0ZFabxSWbWovY3uYSQ2pTgmZeNu2VS4cg".to_owned());
+        params.insert("oauth_signature_method".to_owned(), "HMAC-SHA1".to_owned());
+        params.insert("oauth_timestamp".to_owned(), "1318622958".to_owned());
+        params.insert("oauth_token".to_owned(), "370773112-GmHxMAgYyLbNEtIKZeRNFsMKPR9EyMZeS9weJAEb".to_owned());
+        params.insert("oauth_version".to_owned(), "1.0".to_owned());
+        let signature = signature(
+            "POST",
+            "https://api.twitter.com/1.1/statuses/update.json",
+            &params,
+            "kAcSOqF21Fu85e7zjz7ZN2U4ZRhfV3WpwPAoE3Z7kBw",
+            "LswwdoUaIvS8ltyTt5jkRh4J50vUPVVHtR2YPi5kE",
+        );
+        assert_eq!(signature, "hCtSmYh+iHYCEqBWrE7C7hYmtUk=");
+    }
+
+    #[test]
+    fn oauth_base_url_strips_version() {
+        assert_eq!(oauth_base_url("https://api.twitter.com/1.1"), "https://api.twitter.com");
+        assert_eq!(oauth_base_url("http://localhost:8080/mock/1.1"), "http://localhost:8080/mock");
+        assert_eq!(oauth_base_url("http://127.0.0.1:8080"), "http://127.0.0.1:8080");
+        assert_eq!(oauth_base_url("http://localhost:8080/mock"), "http://localhost:8080/mock");
+    }
+
+    #[test]
+    fn decode_form_of_token_response() {
+        let body = decode_form("oauth_token=abc%2Bd&oauth_token_secret=x+y&screen_name=user\n");
+        assert_eq!(body.get("oauth_token").map(|value| value.as_str()), Some("abc+d"));
+        assert_eq!(body.get("oauth_token_secret").map(|value| value.as_str()), Some("x y"));
+        assert_eq!(token_pair(&body).unwrap(), ("abc+d".to_owned(), "x y".to_owned()));
+        assert_eq!(body.get("screen_name").map(|value| value.as_str()), Some("user"));
+    }
+
+    #[test]
+    fn percent_decode_round_trip() {
+        let text = "a b&c=d/~é%";
+        assert_eq!(percent_decode(&percent_encode(text)), text);
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz"), "%zz");
+    }
+
+    #[test]
+    fn token_pair_requires_both() {
+        let body = decode_form("oauth_token=abc");
+        assert!(token_pair(&body).is_err());
+    }
+}
@@ -1,12 +1,15 @@
 extern crate egg_mode;
 
+use gtk;
+use gtk::prelude::*;
 use std::io;
 
 // AuthError
 #[derive(Debug)]
 pub enum AuthError {
     Io(io::Error),
-    EggMode(egg_mode::error::Error),
+    Api(::api::ApiError),
+    Cancel,
 }
 
 impl From<io::Error> for AuthError {
@@ -15,15 +18,17 @@ impl From<io::Error> for AuthError {
     }
 }
 
-impl From<egg_mode::error::Error> for AuthError {
-    fn from(err: egg_mode::error::Error) -> AuthError {
-        AuthError::EggMode(err)
+impl From<::api::ApiError> for AuthError {
+    fn from(err: ::api::ApiError) -> AuthError {
+        AuthError::Api(err)
     }
 }
 
-pub fn authorize(consumer: egg_mode::KeyPair<'static>) -> Result<egg_mode::Token<'static>, AuthError> {
-    let request_token = try!(egg_mode::request_token(&consumer, "rustytwit"));
-    let url = egg_mode::authorize_url(&request_token);
+// authorize runs PIN based authorization on stdin, tokens are requested from the configured REST API
+pub fn authorize(config: &::config::Config) -> Result<egg_mode::Token<'static>, AuthError> {
+    let client = ::api::Client::consumer(config);
+    let request_token = try!(client.request_token());
+    let url = client.authorize_url(&request_token);
 
     println!("access the following url: {}", url);
     println!("PIN: ");
@@ -32,7 +37,143 @@ pub fn authorize(consumer: egg_mode::KeyPair<'static>) -> Result<egg_mode::Token
     try!(io::stdin().read_line(&mut input));
     let pin = input.trim().to_string();
 
-    // There are access_token, user_id, username receiving here
-    let (token, _, _) = try!(egg_mode::access_token(consumer, &request_token, pin));
+    // the access token is received with the screen name of the authorized user
+    let (token, _) = try!(client.access_token(&request_token, &pin));
     Ok(token)
 }
+
+// authorize_dialog runs PIN based authorization with a modal dialog instead of stdin,
+// and returns the token with the screen name of the authorized user.
+// Errors are shown in the dialog and the authorization is retried until it succeeds or is cancelled
+pub fn authorize_dialog(
+    parent: Option<&gtk::Window>,
+    config: &::config::Config,
+) -> Result<(egg_mode::Token<'static>, String), AuthError> {
+    let client = ::api::Client::consumer(config);
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("rustytwit authorization"),
+        parent,
+        gtk::DIALOG_MODAL | gtk::DIALOG_DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel.into()),
+            ("Authorize", gtk::ResponseType::Ok.into()),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Ok.into());
+
+    let content_area = dialog.get_content_area();
+    content_area.set_spacing(6);
+    content_area.set_border_width(12);
+
+    let label_description = gtk::Label::new(Some(
+        "Open the following url in your browser, authorize rustytwit and enter the displayed PIN.",
+    ));
+    label_description.set_line_wrap(true);
+    label_description.set_xalign(0.0);
+
+    let link_button = gtk::LinkButton::new("");
+
+    let entry_pin = gtk::Entry::new();
+    entry_pin.set_placeholder_text(Some("PIN"));
+    entry_pin.set_activates_default(true);
+
+    let label_error = gtk::Label::new(None);
+    label_error.set_line_wrap(true);
+    label_error.set_xalign(0.0);
+
+    content_area.pack_start(&label_description, false, false, 0);
+    content_area.pack_start(&link_button, false, false, 0);
+    content_area.pack_start(&entry_pin, false, false, 0);
+    content_area.pack_start(&label_error, false, false, 0);
+    dialog.show_all();
+
+    // request_token is None when requesting it failed, Authorize requests it again then
+    let mut request_token = None;
+    loop {
+        if request_token.is_none() {
+            match client.request_token() {
+                Ok(token) => {
+                    let url = client.authorize_url(&token);
+                    link_button.set_uri(&url);
+                    link_button.set_label(&url);
+                    link_button.set_sensitive(true);
+                    request_token = Some(token);
+                },
+                Err(err) => {
+                    error!("{:?}", err);
+                    link_button.set_sensitive(false);
+                    label_error.set_text(&format!(
+                        "Requesting a token failed, Authorize retries: {}",
+                        ::errorbar::describe(&err)
+                    ));
+                },
+            }
+        }
+
+        let response = dialog.run();
+        if response != gtk::ResponseType::Ok.into() {
+            dialog.destroy();
+            return Err(AuthError::Cancel);
+        }
+        let request = match request_token {
+            Some(ref request) => request.clone(),
+            None => continue,
+        };
+        let pin = entry_pin.get_text().unwrap_or(String::new()).trim().to_string();
+        if pin.is_empty() {
+            label_error.set_text("Enter the PIN displayed after the authorization.");
+            continue;
+        }
+
+        // the access token is received with the screen name of the authorized user
+        match client.access_token(&request, &pin) {
+            Ok((token, screen_name)) => {
+                dialog.destroy();
+                info!("authorized as @{}", screen_name);
+                return Ok((token, screen_name));
+            },
+            Err(err) => {
+                // the request token is used once, authorization starts over with a new one
+                error!("{:?}", err);
+                label_error.set_text(&format!(
+                    "Authorization failed, open the new url and retry: {}",
+                    ::errorbar::describe(&err)
+                ));
+                entry_pin.set_text("");
+                request_token = None;
+            },
+        }
+    }
+}
+
+// consumer creates the consumer key pair of config
+pub fn consumer(config: &::config::Config) -> egg_mode::KeyPair<'static> {
+    let (key, secret) = config.consumer();
+    egg_mode::KeyPair::new(key, secret)
+}
+
+// token creates the access token of the active account in config
+pub fn token(config: &::config::Config) -> egg_mode::Token<'static> {
+    let consumer = consumer(config);
+    let access = egg_mode::KeyPair::new(
+        config.toml.access_key.key.borrow().clone(),
+        config.toml.access_key.secret.borrow().clone(),
+    );
+    egg_mode::Token::Access {
+        consumer: consumer,
+        access: access,
+    }
+}
+
+// save_token writes the access key pair of token to config and synchronizes config file
+pub fn save_token(config: &::config::Config, token: &egg_mode::Token<'static>) -> Result<(), ::config::ConfigError> {
+    match *token {
+        egg_mode::Token::Access { ref access, .. } => {
+            config.set_access_key(access.key.to_string(), access.secret.to_string());
+        },
+        egg_mode::Token::Bearer(_) => {
+            return Err(::config::ConfigError::String("bearer token has no access key".to_owned()));
+        },
+    }
+    config.sync()
+}
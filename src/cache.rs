@@ -4,7 +4,9 @@ extern crate glib;
 extern crate rustc_serialize;
 
 
+use rustc_serialize::{Decodable, Encodable};
 use rustc_serialize::json;
+use std::fs;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
@@ -36,22 +38,52 @@ impl From<json::DecoderError> for CacheError {
     }
 }
 
-pub fn write(filename: path::PathBuf, timeline: &Vec<::timeline::home::TimelineRow>) -> Result<(), CacheError> {
+// path returns the cache file of account, the default account keeps the cache directly under cache_dir
+pub fn path(cache_dir: &path::Path, filename: &str, account: &str) -> path::PathBuf {
+    if account == ::config::DEFAULT_ACCOUNT {
+        return cache_dir.join(filename);
+    }
+    cache_dir.join("accounts").join(account).join(filename)
+}
+
+pub fn write<T: Encodable>(filename: path::PathBuf, timeline: &T) -> Result<(), CacheError> {
+    if let Some(parent) = filename.parent() {
+        try!(fs::create_dir_all(parent));
+    }
     try!(File::create(filename.clone())?.write_all(json::encode(timeline)?.as_bytes()));
     info!("wrote cache to {:?}", filename);
     Ok(())
 }
 
-pub fn load(filename: path::PathBuf) -> Result<Vec<::timeline::home::TimelineRow>, CacheError> {
+// load reads the cache of filename, and returns the empty one when it is not written yet
+pub fn load<T: Decodable + Default>(filename: path::PathBuf) -> Result<T, CacheError> {
     let mut file = match File::open(filename) {
         Ok(file) => file,
-        Err(_) => {
-            let home: Vec<::timeline::home::TimelineRow> = Vec::new();
-            return Ok(home);
-        },
+        Err(_) => return Ok(T::default()),
     };
 
     let mut body = String::new();
     try!(file.read_to_string(&mut body));
     Ok(try!(json::decode(body.as_str()).map_err(CacheError::JsonDecoder)))
 }
+
+// switch writes timeline to the cache of account from, and replaces it by the cache of account to
+pub fn switch<T: Encodable + Decodable + Default>(
+    cache_dir: &path::Path,
+    filename: &str,
+    from: &str,
+    to: &str,
+    timeline: &mut T,
+) {
+    match write(path(cache_dir, filename, from), timeline) {
+        Ok(_) => (),
+        Err(err) => error!("{:?}", err),
+    }
+    *timeline = match load(path(cache_dir, filename, to)) {
+        Ok(timeline) => timeline,
+        Err(err) => {
+            error!("{:?}", err);
+            T::default()
+        },
+    };
+}
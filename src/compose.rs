@@ -0,0 +1,824 @@
+extern crate chrono;
+extern crate egg_mode;
+extern crate regex;
+
+use chrono::{Local, TimeZone};
+use gdk;
+use gtk;
+use gtk::prelude::*;
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::mem;
+use std::path;
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{TryRecvError, channel};
+use std::thread;
+use std::time;
+use timeline::home::{TimelineRow, Tweet};
+
+// MAX_WEIGHTED_LENGTH is the maximum weighted length of a tweet
+pub const MAX_WEIGHTED_LENGTH: usize = 280;
+
+// TCO_LENGTH is the length of a url wrapped by t.co
+const TCO_LENGTH: usize = 23;
+
+// THREAD_BREAK is the line which separates tweets of a thread in the compose dialog
+pub const THREAD_BREAK: &'static str = "---";
+
+// NUMBER_LENGTH is the length reserved for the number like " 12/34" appended to each tweet of a thread
+const NUMBER_LENGTH: usize = 6;
+
+// SCHEDULE_FORMAT is the format of the local time which a scheduled tweet is posted at
+pub const SCHEDULE_FORMAT: &'static str = "%Y-%m-%d %H:%M";
+
+// POLL_MILLIS is the interval which the dialog checks the posting worker at when no event is pending
+const POLL_MILLIS: u64 = 50;
+
+// Draft is the tweet edited by the compose dialog, it is kept in the outbox until it is posted
+#[derive(Clone, Debug, Default, RustcEncodable, RustcDecodable)]
+pub struct Draft {
+    pub text: String,
+    pub in_reply_to_status_id: Option<u64>,
+    // attachment_url is the url of the quoted tweet, it is not counted in the length of text
+    pub attachment_url: Option<String>,
+    pub media: Vec<::media::Media>,
+}
+
+impl Draft {
+    // reply returns the draft of the reply to the original tweet, it mentions the author
+    pub fn reply(tweet: &Tweet) -> Draft {
+        Draft {
+            text: format!("@{} ", tweet.author().screen_name),
+            in_reply_to_status_id: Some(tweet.status_id()),
+            attachment_url: None,
+            media: Vec::new(),
+        }
+    }
+
+    // quote returns the draft of the quote of the original tweet
+    pub fn quote(tweet: &Tweet) -> Draft {
+        Draft {
+            text: String::new(),
+            in_reply_to_status_id: None,
+            attachment_url: Some(::timeline::actions::status_url(&tweet.author().screen_name, tweet.status_id())),
+            media: Vec::new(),
+        }
+    }
+}
+
+// Sent is the result of the compose dialog
+pub enum Sent {
+    Posted(TimelineRow),
+    // Queued is the tweet or the thread to post later, at the scheduled unix time or when the network is available
+    Queued(Thread, Option<i64>),
+    // Held is the thread left unfinished by the error when the dialog is closed, it is resumed from the outbox
+    Held(Thread, String),
+}
+
+// Posting is the message from the worker posting a thread to the compose dialog
+enum Posting {
+    Progress(f64),
+    Posted(TimelineRow),
+    Done(Thread, Result<(), ::media::MediaError>),
+}
+
+// post uploads media of draft and posts it, progress is called with the ratio of uploaded media
+pub fn post<F: Fn(f64)>(
+    client: &::api::Client,
+    draft: &Draft,
+    progress: F,
+) -> Result<TimelineRow, ::media::MediaError> {
+    let mut media_ids = Vec::new();
+    for (index, media) in draft.media.iter().enumerate() {
+        let count = draft.media.len() as f64;
+        media_ids.push(try!(::media::upload(client, media, |fraction| progress((index as f64 + fraction) / count))));
+    }
+    Ok(try!(update(client, draft, &media_ids)))
+}
+
+// update posts draft with uploaded media_ids as a tweet and returns it as a read row
+pub fn update(client: &::api::Client, draft: &Draft, media_ids: &Vec<String>) -> Result<TimelineRow, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("status".to_owned(), draft.text.clone());
+    params.insert("tweet_mode".to_owned(), "extended".to_owned());
+    if let Some(in_reply_to_status_id) = draft.in_reply_to_status_id {
+        params.insert("in_reply_to_status_id".to_owned(), format!("{}", in_reply_to_status_id));
+    }
+    if let Some(ref attachment_url) = draft.attachment_url {
+        params.insert("attachment_url".to_owned(), attachment_url.clone());
+    }
+    if !media_ids.is_empty() {
+        params.insert("media_ids".to_owned(), media_ids.join(","));
+    }
+    let mut row = try!(TimelineRow::from_json(&try!(client.post("statuses/update.json", &params))));
+    row.unread = false;
+    Ok(row)
+}
+
+thread_local! {
+    // URL_RE matches urls which twitter wraps by t.co. a domain without scheme is linked when its tld is generic,
+    // or when a path follows its country code tld
+    static URL_RE: Regex = Regex::new(concat!(
+        r"(?i)\b(?:https?://\S+|(?:[a-z0-9][a-z0-9-]*\.)+",
+        r"(?:(?:com|net|org|edu|gov|info|biz|io|co|me|tv|ly|app|dev|xyz)\b(?:/\S*)?|[a-z]{2}/\S*))"
+    )).unwrap();
+}
+
+// weighted_length returns the length of text counted by twitter, a url is counted as t.co length
+// and a character out of latin and general punctuation ranges is counted as 2
+pub fn weighted_length(text: &str) -> usize {
+    URL_RE.with(|url_re| {
+        let mut length = 0;
+        let mut last = 0;
+        for (start, end) in url_re.find_iter(text) {
+            // the domain of a mail address is not linked
+            if text[..start].ends_with('@') {
+                continue;
+            }
+            length += text[last..start].chars().map(weight).sum::<usize>() + TCO_LENGTH;
+            last = end;
+        }
+        length + text[last..].chars().map(weight).sum::<usize>()
+    })
+}
+
+fn weight(c: char) -> usize {
+    match c as u32 {
+        0...4351 | 8192...8205 | 8208...8223 | 8242...8247 => 1,
+        _ => 2,
+    }
+}
+
+// Thread is a reply chain of drafts, posting it is resumed from posted after a post fails.
+// a single tweet is a thread of one draft, and drafts are numbered when they are posted
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct Thread {
+    pub drafts: Vec<Draft>,
+    pub posted: usize,
+}
+
+impl Thread {
+    // new chains parts after draft, the reply, the quote and media of draft belong to the first tweet
+    pub fn new(draft: &Draft, parts: &Vec<String>) -> Thread {
+        let drafts = parts
+            .iter()
+            .cloned()
+            .enumerate()
+            .map(|(index, text)| match index {
+                0 => Draft { text: text, ..draft.clone() },
+                _ => Draft { text: text, ..Draft::default() },
+            })
+            .collect();
+        Thread {
+            drafts: drafts,
+            posted: 0,
+        }
+    }
+
+    // single returns the thread of one tweet, it is not numbered
+    pub fn single(draft: Draft) -> Thread {
+        Thread {
+            drafts: vec![draft],
+            posted: 0,
+        }
+    }
+
+    // draft returns the draft which the compose dialog edits, tweets of a thread are joined by THREAD_BREAK lines,
+    // and media of all tweets are attached to it
+    pub fn draft(&self) -> Draft {
+        let text = self.drafts
+            .iter()
+            .map(|draft| draft.text.as_str())
+            .collect::<Vec<_>>()
+            .join(&format!("\n{}\n", THREAD_BREAK));
+        let media = self.drafts.iter().flat_map(|draft| draft.media.iter().cloned()).collect();
+        Draft {
+            text: text,
+            media: media,
+            ..self.drafts.first().cloned().unwrap_or_default()
+        }
+    }
+
+    // advance marks the next tweet as posted as row, the following one replies to it
+    pub fn advance(&mut self, row: &TimelineRow) {
+        self.posted += 1;
+        if let Some(next) = self.drafts.get_mut(self.posted) {
+            next.in_reply_to_status_id = Some(row.tweet.id);
+        }
+    }
+
+    // post posts remaining tweets in order, each one replies to the previous one.
+    // posted is called with each posted tweet, and posting stops at the failed one
+    pub fn post<F: FnMut(TimelineRow), P: Fn(f64)>(
+        &mut self,
+        client: &::api::Client,
+        progress: P,
+        mut posted: F,
+    ) -> Result<(), ::media::MediaError> {
+        let count = self.drafts.len() as f64;
+        while self.posted < self.drafts.len() {
+            let index = self.posted;
+            let mut draft = self.drafts[index].clone();
+            if self.drafts.len() > 1 {
+                draft.text = number(&draft.text, index, self.drafts.len());
+            }
+            let row = try!(post(client, &draft, |fraction| progress((index as f64 + fraction) / count)));
+            self.advance(&row);
+            progress(self.posted as f64 / count);
+            posted(row);
+        }
+        Ok(())
+    }
+}
+
+// split_thread splits text into parts which fit in numbered tweets, at paragraph or sentence boundaries.
+// a sentence longer than a tweet is split at spaces, and a word longer than it is split anywhere
+pub fn split_thread(text: &str) -> Vec<String> {
+    let limit = MAX_WEIGHTED_LENGTH - NUMBER_LENGTH;
+    let paragraphs = text
+        .split("\n\n")
+        .map(|paragraph| paragraph.trim())
+        .filter(|paragraph| !paragraph.is_empty())
+        .flat_map(|paragraph| {
+            let sentences = sentences(paragraph).into_iter().flat_map(|sentence| fit(&sentence, limit)).collect();
+            pack(sentences, " ", limit)
+        })
+        .collect();
+    pack(paragraphs, "\n\n", limit)
+}
+
+// thread_parts returns parts of text separated by THREAD_BREAK lines
+pub fn thread_parts(text: &str) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    for line in text.lines() {
+        if line.trim() == THREAD_BREAK {
+            parts.push(String::new());
+            continue;
+        }
+        if let Some(part) = parts.last_mut() {
+            part.push_str(line);
+            part.push('\n');
+        }
+    }
+    parts.into_iter().map(|part| part.trim().to_owned()).filter(|part| !part.is_empty()).collect()
+}
+
+// numbered appends the number like " 1/3" to each part
+pub fn numbered(parts: &Vec<String>) -> Vec<String> {
+    parts.iter().enumerate().map(|(index, part)| number(part, index, parts.len())).collect()
+}
+
+fn number(part: &str, index: usize, count: usize) -> String {
+    format!("{} {}/{}", part, index + 1, count)
+}
+
+fn sentences(paragraph: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut sentence = String::new();
+    let mut chars = paragraph.chars().peekable();
+    while let Some(c) = chars.next() {
+        sentence.push(c);
+        let terminated = match c {
+            '.' | '!' | '?' => chars.peek().map(|next| next.is_whitespace()).unwrap_or(true),
+            '。' | '！' | '？' => true,
+            _ => false,
+        };
+        if terminated {
+            sentences.push(mem::replace(&mut sentence, String::new()).trim().to_owned());
+        }
+    }
+    sentences.push(sentence.trim().to_owned());
+    sentences.into_iter().filter(|sentence| !sentence.is_empty()).collect()
+}
+
+// fit splits sentence into pieces within limit, at spaces or anywhere in a long word
+fn fit(sentence: &str, limit: usize) -> Vec<String> {
+    if weighted_length(sentence) <= limit {
+        return vec![sentence.to_owned()];
+    }
+    let mut pieces = Vec::new();
+    for word in sentence.split_whitespace() {
+        if weighted_length(word) <= limit {
+            pieces.push(word.to_owned());
+            continue;
+        }
+        let mut piece = String::new();
+        for c in word.chars() {
+            if weighted_length(&piece) + weight(c) > limit {
+                pieces.push(mem::replace(&mut piece, String::new()));
+            }
+            piece.push(c);
+        }
+        pieces.push(piece);
+    }
+    pack(pieces, " ", limit)
+}
+
+// pack joins units by separator while the joined text is within limit
+fn pack(units: Vec<String>, separator: &str, limit: usize) -> Vec<String> {
+    let mut packed = Vec::new();
+    let mut current = String::new();
+    for unit in units {
+        if current.is_empty() {
+            current = unit;
+            continue;
+        }
+        let joined = format!("{}{}{}", current, separator, unit);
+        if weighted_length(&joined) <= limit {
+            current = joined;
+        } else {
+            packed.push(mem::replace(&mut current, unit));
+        }
+    }
+    if !current.is_empty() {
+        packed.push(current);
+    }
+    packed
+}
+
+// show runs the compose dialog of draft until the tweet is posted or queued, or the dialog is closed,
+// sent is called with the posted tweet or the queued draft. draft is scheduled at scheduled_at when it is reopened
+// from the outbox, and true is returned when any of it is sent, the reopened entry is replaced then
+pub fn show<F: Fn(Sent)>(
+    parent: &gtk::Window,
+    config: Arc<::config::Config>,
+    token: Arc<Mutex<egg_mode::Token<'static>>>,
+    media_dir: path::PathBuf,
+    draft: Draft,
+    scheduled_at: Option<i64>,
+    sent: F,
+) -> bool {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("compose"),
+        Some(parent),
+        gtk::DIALOG_MODAL | gtk::DIALOG_DESTROY_WITH_PARENT,
+        &[("Cancel", gtk::ResponseType::Cancel.into())],
+    );
+    let button_send = dialog.add_button("Send", gtk::ResponseType::Accept.into());
+    let content_area = dialog.get_content_area();
+    content_area.set_spacing(6);
+    content_area.set_border_width(12);
+
+    let text_view = gtk::TextView::new();
+    text_view.set_wrap_mode(gtk::WrapMode::WordChar);
+    text_view.set_size_request(400, 120);
+    let buffer = match text_view.get_buffer() {
+        Some(buffer) => buffer,
+        None => {
+            error!("buffer of text_view is None");
+            return false;
+        },
+    };
+
+    let spinner = gtk::Spinner::new();
+    let label_error = gtk::Label::new(None);
+    label_error.set_line_wrap(true);
+    label_error.set_xalign(0.0);
+    let label_counter = gtk::Label::new(Some(format!("0 / {}", MAX_WEIGHTED_LENGTH).as_str()));
+    let media_box = ::media::MediaBox::new(media_dir.clone());
+    let button_attach = gtk::Button::new_from_icon_name("mail-attachment", 1);
+    button_attach.set_tooltip_text(Some("Attach images, a GIF or a video"));
+
+    let check_schedule = gtk::CheckButton::new_with_label("Schedule at");
+    let entry_schedule = gtk::Entry::new();
+    entry_schedule.set_placeholder_text(Some("YYYY-MM-DD HH:MM"));
+    entry_schedule.set_text(&format!("{}", Local::now().format(SCHEDULE_FORMAT)));
+    entry_schedule.set_sensitive(false);
+    let check_thread = gtk::CheckButton::new_with_label("Thread");
+    // a thread reopened from the outbox is edited in thread mode
+    check_thread.set_active(thread_parts(&draft.text).len() > 1);
+    check_thread.set_tooltip_text(Some(
+        format!("Split text into a reply chain, a line of {} breaks tweets", THREAD_BREAK).as_str(),
+    ));
+    let box_schedule = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    box_schedule.pack_start(&check_schedule, false, false, 0);
+    box_schedule.pack_start(&entry_schedule, false, false, 0);
+    box_schedule.pack_end(&check_thread, false, false, 0);
+
+    let box_status = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    box_status.pack_start(&button_attach, false, false, 0);
+    box_status.pack_start(&spinner, false, false, 0);
+    box_status.pack_start(&label_error, true, true, 0);
+    box_status.pack_start(&label_counter, false, false, 0);
+
+    // the replied or quoted tweet is shown above text
+    let context = match (draft.in_reply_to_status_id, draft.attachment_url.as_ref()) {
+        (Some(_), _) => Some(format!("Reply to {}", draft.text.trim())),
+        (None, Some(attachment_url)) => Some(format!("Quote {}", attachment_url)),
+        (None, None) => None,
+    };
+    if let Some(context) = context {
+        let label_context = gtk::Label::new(Some(context.as_str()));
+        label_context.set_xalign(0.0);
+        content_area.pack_start(&label_context, false, false, 0);
+    }
+    content_area.pack_start(&text_view, true, true, 0);
+    content_area.pack_start(&media_box.container, false, false, 0);
+    content_area.pack_start(&box_schedule, false, false, 0);
+    content_area.pack_start(&box_status, false, false, 0);
+
+    // event definition, when schedule is toggled
+    {
+        let entry_schedule = entry_schedule.clone();
+
+        check_schedule.connect_toggled(move |check| entry_schedule.set_sensitive(check.get_active()));
+    }
+
+    // event definition, when thread is toggled, text is split by THREAD_BREAK lines or joined again
+    {
+        let buffer = buffer.clone();
+
+        check_thread.connect_toggled(move |check| {
+            let text = buffer_text(&buffer);
+            let text = match check.get_active() {
+                true => split_thread(&text).join(&format!("\n{}\n", THREAD_BREAK)),
+                false => thread_parts(&text).join("\n\n"),
+            };
+            buffer.set_text(&text);
+        });
+    }
+
+    // event definition, when attach button is clicked
+    {
+        let dialog = dialog.clone();
+        let media_box = media_box.clone();
+        let label_error = label_error.clone();
+
+        button_attach.connect_clicked(move |_| {
+            let chooser = gtk::FileChooserDialog::new(Some("attach"), Some(&dialog), gtk::FileChooserAction::Open);
+            chooser.add_button("Cancel", gtk::ResponseType::Cancel.into());
+            chooser.add_button("Attach", gtk::ResponseType::Accept.into());
+            chooser.set_select_multiple(true);
+            if chooser.run() == gtk::ResponseType::Accept.into() {
+                for filename in chooser.get_filenames() {
+                    if let Some(filename) = filename.to_str() {
+                        attach(&media_box, &label_error, filename);
+                    }
+                }
+            }
+            chooser.destroy();
+        });
+    }
+
+    // event definition, when files are dropped on the dialog
+    {
+        let media_box = media_box.clone();
+        let label_error = label_error.clone();
+
+        let targets = vec![gtk::TargetEntry::new("text/uri-list", gtk::TargetFlags::empty(), 0)];
+        dialog.drag_dest_set(gtk::DEST_DEFAULT_ALL, &targets, gdk::ACTION_COPY);
+        dialog.connect_drag_data_received(move |_, _, _, _, data, _, _| for uri in data.get_uris() {
+            if let Some(filename) = ::media::uri_filename(&uri) {
+                attach(&media_box, &label_error, &filename);
+            }
+        });
+    }
+
+    // event definition, when an image is pasted, text is pasted by text_view itself
+    {
+        let media_box = media_box.clone();
+        let label_error = label_error.clone();
+
+        text_view.connect_key_press_event(move |_, event| {
+            if !event.get_state().contains(gdk::CONTROL_MASK) || event.get_keyval() != gdk::enums::key::v {
+                return Inhibit(false);
+            }
+            let clipboard = gtk::Clipboard::get(&gdk::Atom::intern("CLIPBOARD"));
+            if !clipboard.wait_is_image_available() {
+                return Inhibit(false);
+            }
+            if let Some(pixbuf) = clipboard.wait_for_image() {
+                match media_box.attach_pixbuf(&pixbuf) {
+                    Ok(_) => label_error.set_text(""),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        label_error.set_text(&::media::describe(&err));
+                    },
+                }
+            }
+            Inhibit(true)
+        });
+    }
+
+    // event definition, when text is changed, the longest tweet is counted in a thread
+    {
+        let label_counter = label_counter.clone();
+        let button_send = button_send.clone();
+        let check_thread = check_thread.clone();
+
+        buffer.connect_changed(move |buffer| {
+            let text = buffer_text(buffer);
+            let (length, tweets) = match check_thread.get_active() {
+                true => {
+                    let parts = numbered(&thread_parts(&text));
+                    (parts.iter().map(|part| weighted_length(part)).max().unwrap_or(0), parts.len())
+                },
+                false => (weighted_length(&text), 1),
+            };
+            let prefix = match tweets {
+                1 => String::new(),
+                tweets => format!("{} tweets, ", tweets),
+            };
+            if length > MAX_WEIGHTED_LENGTH {
+                label_counter.set_markup(&format!(
+                    r#"{}<span foreground="red">{}</span> / {}"#,
+                    prefix,
+                    length,
+                    MAX_WEIGHTED_LENGTH
+                ));
+            } else {
+                label_counter.set_text(&format!("{}{} / {}", prefix, length, MAX_WEIGHTED_LENGTH));
+            }
+            button_send.set_sensitive(length <= MAX_WEIGHTED_LENGTH);
+        });
+    }
+
+    buffer.set_text(&draft.text);
+    media_box.set_media(draft.media.clone());
+    if let Some(scheduled_at) = scheduled_at {
+        entry_schedule.set_text(&format!("{}", Local.timestamp(scheduled_at, 0).format(SCHEDULE_FORMAT)));
+        check_schedule.set_active(true);
+    }
+
+    // thread is kept after a post of it fails, and sending resumes it
+    let mut thread: Option<Thread> = None;
+    // failure is the error which left thread unfinished, the thread is held in the outbox when the dialog is closed
+    let mut failure = String::new();
+    // pasted images are removed when the dialog is closed unless the tweet is queued with them
+    let mut queued = false;
+    // posted is set when the whole tweet or thread is posted
+    let mut posted = false;
+
+    dialog.show_all();
+    while dialog.run() == gtk::ResponseType::Accept.into() {
+        let draft = Draft {
+            text: buffer_text(&buffer),
+            media: media_box.media(),
+            ..draft.clone()
+        };
+        if draft.text.trim().is_empty() && draft.media.is_empty() {
+            label_error.set_text("The tweet is empty.");
+            continue;
+        }
+        if !::media::can_attach_all(&draft.media) {
+            label_error.set_text(&format!(
+                "Up to {} images or one GIF or video can be attached.",
+                ::media::MAX_IMAGES
+            ));
+            continue;
+        }
+
+        let current = match (thread.take(), check_thread.get_active()) {
+            (Some(current), _) => current,
+            (None, false) => Thread::single(draft.clone()),
+            (None, true) => {
+                let parts = thread_parts(&draft.text);
+                if parts.is_empty() {
+                    label_error.set_text("The tweet is empty.");
+                    continue;
+                }
+                Thread::new(&draft, &parts)
+            },
+        };
+
+        // a scheduled tweet or thread is queued without posting
+        if check_schedule.get_active() {
+            let text = entry_schedule.get_text().unwrap_or(String::new());
+            match Local.datetime_from_str(text.trim(), SCHEDULE_FORMAT) {
+                Ok(scheduled_at) if scheduled_at > Local::now() => {
+                    sent(Sent::Queued(current, Some(scheduled_at.timestamp())));
+                    queued = true;
+                    break;
+                },
+                Ok(_) => label_error.set_text("The scheduled time is past."),
+                Err(_) => label_error.set_text("The scheduled time is not YYYY-MM-DD HH:MM."),
+            }
+            continue;
+        }
+
+        // the dialog is busy until the request returns, pending events are drawn before it
+        text_view.set_sensitive(false);
+        button_send.set_sensitive(false);
+        button_attach.set_sensitive(false);
+        check_thread.set_sensitive(false);
+        label_error.set_text("");
+        spinner.start();
+        if !draft.media.is_empty() || current.drafts.len() > 1 {
+            media_box.progress_bar.set_fraction(0.0);
+            media_box.progress_bar.show();
+        }
+        while gtk::events_pending() {
+            gtk::main_iteration();
+        }
+
+        // the thread is posted on a worker thread, the dialog is drawn while progress and posted tweets arrive
+        let client = ::api::client(&config, &token);
+        let (tx, rx) = channel();
+        let mut fallback = current.clone();
+        thread::spawn(move || {
+            let mut current = current;
+            let result = {
+                let progress = |fraction| {
+                    let _ = tx.send(Posting::Progress(fraction));
+                };
+                current.post(&client, &progress, |row| {
+                    let _ = tx.send(Posting::Posted(row));
+                })
+            };
+            let _ = tx.send(Posting::Done(current, result));
+        });
+        let (current, result) = loop {
+            match rx.try_recv() {
+                Ok(Posting::Progress(fraction)) => media_box.progress_bar.set_fraction(fraction),
+                Ok(Posting::Posted(row)) => {
+                    fallback.advance(&row);
+                    sent(Sent::Posted(row));
+                },
+                Ok(Posting::Done(current, result)) => break (current, result),
+                Err(TryRecvError::Empty) => match gtk::events_pending() {
+                    true => {
+                        gtk::main_iteration();
+                    },
+                    false => thread::sleep(time::Duration::from_millis(POLL_MILLIS)),
+                },
+                // the worker panicked, the thread is resumed after the tweets posted before it
+                Err(TryRecvError::Disconnected) => {
+                    let err = ::media::MediaError::Processing("posting stopped unexpectedly".to_owned());
+                    break (fallback, Err(err));
+                },
+            }
+        };
+        spinner.stop();
+        media_box.progress_bar.hide();
+        text_view.set_sensitive(true);
+        button_send.set_sensitive(true);
+        button_attach.set_sensitive(true);
+        check_thread.set_sensitive(true);
+        match result {
+            Ok(_) => {
+                posted = true;
+                break;
+            },
+            // the rest of the tweet or the thread is queued in the outbox while the network is down
+            Err(ref err) if ::outbox::is_offline(err) => {
+                error!("{:?}", err);
+                sent(Sent::Queued(current, None));
+                queued = true;
+                break;
+            },
+            Err(ref err) if current.posted == 0 => {
+                error!("{:?}", err);
+                label_error.set_text(&format!("Failed to post the tweet: {}", ::media::describe(err)));
+            },
+            // posted tweets of the thread are not edited, sending resumes it from the failed one
+            Err(err) => {
+                error!("{:?}", err);
+                failure = ::media::describe(&err);
+                label_error.set_text(&format!(
+                    "Posted {} of {} tweets, Send resumes the thread: {}",
+                    current.posted,
+                    current.drafts.len(),
+                    failure
+                ));
+                text_view.set_sensitive(false);
+                button_attach.set_sensitive(false);
+                check_thread.set_sensitive(false);
+                check_schedule.set_sensitive(false);
+                entry_schedule.set_sensitive(false);
+                thread = Some(current);
+            },
+        }
+    }
+    // the unfinished thread is left in the outbox to be resumed there
+    if let Some(thread) = thread {
+        sent(Sent::Held(thread, failure));
+        queued = true;
+    }
+    // pasted images of the reopened draft are removed only when it is replaced by the sent one
+    let attached = media_box.media();
+    let removed: Vec<::media::Media> = match (posted, queued) {
+        (true, _) => attached.iter().chain(draft.media.iter()).cloned().collect(),
+        (false, true) => draft.media.iter().filter(|media| !contains(&attached, media)).cloned().collect(),
+        (false, false) => attached.iter().filter(|media| !contains(&draft.media, media)).cloned().collect(),
+    };
+    ::media::remove_pasted(&removed, &media_dir);
+    dialog.destroy();
+    posted || queued
+}
+
+// contains returns true when media has the file of item
+fn contains(media: &Vec<::media::Media>, item: &::media::Media) -> bool {
+    media.iter().any(|media| media.filename == item.filename)
+}
+
+// attach attaches the file of filename to the draft, or shows why it is not attached
+fn attach(media_box: &::media::MediaBox, label_error: &gtk::Label, filename: &str) {
+    match media_box.attach(filename) {
+        Ok(_) => label_error.set_text(""),
+        Err(err) => {
+            error!("{:?}", err);
+            label_error.set_text(&::media::describe(&err));
+        },
+    }
+}
+
+fn buffer_text(buffer: &gtk::TextBuffer) -> String {
+    let (start, end) = buffer.get_bounds();
+    buffer.get_text(&start, &end, false).unwrap_or(String::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_length_counts_url_as_tco_length() {
+        assert_eq!(weighted_length("see https://example.com/a/very/long/path/of/the/page"), 4 + TCO_LENGTH);
+        assert_eq!(weighted_length("http://a.b http://c.d"), 1 + 2 * TCO_LENGTH);
+    }
+
+    #[test]
+    fn weighted_length_counts_domain_without_scheme_as_tco_length() {
+        assert_eq!(weighted_length("see example.com/a/long/path"), 4 + TCO_LENGTH);
+        assert_eq!(weighted_length("www.example.org."), TCO_LENGTH + 1);
+        assert_eq!(weighted_length("example.jp/page"), TCO_LENGTH);
+    }
+
+    #[test]
+    fn weighted_length_skips_non_urls() {
+        assert_eq!(weighted_length("main.rs"), 7);
+        assert_eq!(weighted_length("example.community"), 17);
+        assert_eq!(weighted_length("a@example.com"), 13);
+    }
+
+    #[test]
+    fn weighted_length_counts_cjk_as_2() {
+        assert_eq!(weighted_length("日本語"), 6);
+        assert_eq!(weighted_length("abc日本"), 7);
+    }
+
+    #[test]
+    fn weighted_length_boundary() {
+        let limit = "a".repeat(MAX_WEIGHTED_LENGTH);
+        assert_eq!(weighted_length(&limit), MAX_WEIGHTED_LENGTH);
+        let over = format!("{}日", "a".repeat(MAX_WEIGHTED_LENGTH - 1));
+        assert_eq!(weighted_length(&over), MAX_WEIGHTED_LENGTH + 1);
+    }
+
+    #[test]
+    fn thread_draft_keeps_media_of_all_tweets() {
+        let media = |filename: &str| ::media::Media {
+            filename: filename.to_owned(),
+            media_type: "image/png".to_owned(),
+            alt_text: String::new(),
+        };
+        let mut thread = Thread::new(&Draft::default(), &vec!["first".to_owned(), "second".to_owned()]);
+        thread.drafts[0].media.push(media("a.png"));
+        thread.drafts[1].media.push(media("b.png"));
+        let draft = thread.draft();
+        assert_eq!(draft.text, format!("first\n{}\nsecond", THREAD_BREAK));
+        let filenames: Vec<&str> = draft.media.iter().map(|media| media.filename.as_str()).collect();
+        assert_eq!(filenames, vec!["a.png", "b.png"]);
+    }
+
+    #[test]
+    fn split_thread_keeps_short_paragraphs_together() {
+        assert_eq!(split_thread("First.\n\nSecond."), vec!["First.\n\nSecond.".to_owned()]);
+    }
+
+    #[test]
+    fn split_thread_splits_at_sentences() {
+        let first = format!("{}.", "a".repeat(199));
+        let second = format!("{}.", "b".repeat(199));
+        assert_eq!(split_thread(&format!("{} {}", first, second)), vec![first, second]);
+    }
+
+    #[test]
+    fn split_thread_splits_long_sentence_at_spaces() {
+        let text = vec!["word"; 100].join(" ");
+        let parts = split_thread(&text);
+        assert_eq!(parts.len(), 2);
+        assert!(parts.iter().all(|part| weighted_length(part) <= MAX_WEIGHTED_LENGTH - NUMBER_LENGTH));
+        assert_eq!(parts.join(" "), text);
+    }
+
+    #[test]
+    fn split_thread_splits_long_word_anywhere() {
+        let limit = MAX_WEIGHTED_LENGTH - NUMBER_LENGTH;
+        let parts = split_thread(&"a".repeat(600));
+        assert_eq!(parts, vec!["a".repeat(limit), "a".repeat(limit), "a".repeat(600 - 2 * limit)]);
+    }
+
+    #[test]
+    fn split_thread_numbered_parts_fit() {
+        let text = vec!["word"; 300].join(" ");
+        for part in numbered(&split_thread(&text)) {
+            assert!(weighted_length(&part) <= MAX_WEIGHTED_LENGTH);
+        }
+    }
+
+    #[test]
+    fn thread_parts_split_at_thread_break() {
+        assert_eq!(thread_parts("one\n---\ntwo\nlines\n --- \n\n"), vec!["one".to_owned(), "two\nlines".to_owned()]);
+    }
+}
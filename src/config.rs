@@ -1,16 +1,27 @@
+extern crate chrono;
+extern crate egg_mode;
 extern crate toml;
 
-use std::cell::Cell;
+use chrono::Local;
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::env;
 use std::fs::File;
 use std::io;
 use std::io::prelude::*;
 use std::path;
 use std::str::FromStr;
+use std::sync::Mutex;
 
+// DEFAULT_ACCOUNT is the account name of a config without account profiles
+pub const DEFAULT_ACCOUNT: &'static str = "default";
 
 const DEFAULT: &'static str = r#"
 [general]
 update_timer = false
+update_timer_duration = 600
+url_filter = false
+history = 2000
 
 [access_key]
 key = ""
@@ -20,6 +31,21 @@ secret = ""
 last_update_id = 1
 last_read_id = 1
 limits = 500
+
+[mentions_timeline]
+last_update_id = 1
+last_read_id = 1
+limits = 500
+
+[likes_timeline]
+last_update_id = 1
+last_read_id = 1
+limits = 200
+
+[direct_messages]
+last_update_id = 1
+last_read_id = 1
+limits = 200
 "#;
 
 // ConfigError
@@ -27,6 +53,7 @@ limits = 500
 pub enum ConfigError {
     Io(io::Error),
     TomlParserError(Vec<toml::ParserError>),
+    Secrets(::secrets::SecretsError),
     String(String),
 }
 
@@ -42,6 +69,12 @@ impl From<Vec<toml::ParserError>> for ConfigError {
     }
 }
 
+impl From<::secrets::SecretsError> for ConfigError {
+    fn from(err: ::secrets::SecretsError) -> ConfigError {
+        ConfigError::Secrets(err)
+    }
+}
+
 impl From<String> for ConfigError {
     fn from(err: String) -> ConfigError {
         ConfigError::String(err)
@@ -53,6 +86,18 @@ impl From<String> for ConfigError {
 pub struct Config {
     pub filename: String,
     pub toml: Toml,
+    pub store: RefCell<Option<::secrets::Store>>,
+}
+
+// Snapshot is the state of the active account read by the update thread, config itself is not shared
+// with other threads, and the gtk thread shares a new snapshot when the account, the token or cursors change
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub account: String,
+    pub client: ::api::Client,
+    pub home_timeline: Timeline,
+    pub mentions_timeline: Timeline,
+    pub direct_messages: Timeline,
 }
 
 // Toml struct implementation
@@ -60,7 +105,19 @@ pub struct Config {
 pub struct Toml {
     pub general: General,
     pub access_key: AccessKey,
-    pub home_timeline: HomeTimeline,
+    pub home_timeline: Timeline,
+    pub mentions_timeline: Timeline,
+    pub likes_timeline: Timeline,
+    pub direct_messages: Timeline,
+    pub lists: RefCell<Option<Vec<List>>>,
+    pub searches: RefCell<Option<Vec<Search>>>,
+    pub account: RefCell<Option<String>>,
+    pub accounts: RefCell<Option<Vec<Account>>>,
+    pub secrets: RefCell<Option<Secrets>>,
+    pub api: Option<Api>,
+    // muted is the mute list shared by accounts, an entry is a screen name or a user id prefixed by id:
+    pub muted: RefCell<Option<Vec<String>>>,
+    pub mute_rules: RefCell<Option<Vec<MuteRule>>>,
 }
 
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
@@ -68,21 +125,101 @@ pub struct General {
     pub update_timer: bool,
     pub update_timer_duration: i32,
     pub url_filter: bool,
+    // history is the number of rows kept in memory while scrolling back older tweets
+    pub history: usize,
 }
 
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
 pub struct AccessKey {
-    pub key: String,
-    pub secret: String,
+    pub key: RefCell<String>,
+    pub secret: RefCell<String>,
 }
 
+// Timeline is the cursor of a timeline
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
-pub struct HomeTimeline {
+pub struct Timeline {
     pub last_update_id: Cell<u64>,
     pub last_read_id: Cell<u64>,
     pub limits: Cell<usize>,
 }
-unsafe impl Sync for HomeTimeline {}
+
+// Secrets references the encrypted credential store, access keys are stored in it by account name
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct Secrets {
+    pub filename: String,
+    pub key_file: Option<String>,
+}
+
+// Api overrides the consumer key pair and the base urls of twitter API
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct Api {
+    pub consumer_key: Option<String>,
+    pub consumer_secret: Option<String>,
+    pub rest_base_url: Option<String>,
+    pub stream_base_url: Option<String>,
+    pub upload_base_url: Option<String>,
+}
+
+// Account is a named profile, the active one is copied into access_key and timeline cursors
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct Account {
+    pub name: String,
+    pub access_key: AccessKey,
+    pub home_timeline: Timeline,
+    pub mentions_timeline: Option<Timeline>,
+    pub likes_timeline: Option<Timeline>,
+    pub direct_messages: Option<Timeline>,
+    pub lists: Option<Vec<List>>,
+    pub searches: Option<Vec<Search>>,
+}
+
+// List is an owned or subscribed list of the account with the cursor of its timeline
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct List {
+    pub id: u64,
+    pub full_name: String,
+    pub timeline: Timeline,
+}
+
+// Search is a saved search of the account with the cursor of its timeline
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct Search {
+    pub query: String,
+    pub timeline: Timeline,
+}
+
+// MuteRule hides tweets whose text contains pattern case-insensitively, pattern is a regex when regex is set
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct MuteRule {
+    pub pattern: String,
+    pub regex: bool,
+    // expires_at is unix time when the rule is removed, None keeps it forever
+    pub expires_at: Option<i64>,
+    pub retweets_only: bool,
+    pub non_followed_only: bool,
+}
+
+impl MuteRule {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map(|expires_at| expires_at <= Local::now().timestamp()).unwrap_or(false)
+    }
+}
+
+impl Timeline {
+    pub fn new(limits: usize) -> Timeline {
+        Timeline {
+            last_update_id: Cell::new(1),
+            last_read_id: Cell::new(1),
+            limits: Cell::new(limits),
+        }
+    }
+
+    pub fn set(&self, timeline: &Timeline) {
+        self.last_update_id.set(timeline.last_update_id.get());
+        self.last_read_id.set(timeline.last_read_id.get());
+        self.limits.set(timeline.limits.get());
+    }
+}
 
 
 impl Config {
@@ -103,7 +240,9 @@ impl Config {
         let mut body = String::new();
         try!(file.read_to_string(&mut body));
 
-        let value = try!(toml::Value::from_str(body.as_ref()));
+        // sections and keys missing in config file are filled by DEFAULT
+        let mut value = try!(toml::Value::from_str(DEFAULT));
+        merge(&mut value, try!(toml::Value::from_str(body.as_ref())));
         let toml = try!(toml::decode(value).ok_or(
             "failed to decode toml value".to_owned(),
         ));
@@ -113,15 +252,407 @@ impl Config {
         let config: Config = Config {
             filename: filename,
             toml: toml,
+            store: RefCell::new(None),
         };
         return Ok(config);
     }
 
+    // snapshot returns the state of the active account with the client of token
+    pub fn snapshot(&self, token: &Mutex<egg_mode::Token<'static>>) -> Snapshot {
+        Snapshot {
+            account: self.account(),
+            client: ::api::client(self, token),
+            home_timeline: self.toml.home_timeline.clone(),
+            mentions_timeline: self.toml.mentions_timeline.clone(),
+            direct_messages: self.toml.direct_messages.clone(),
+        }
+    }
+
+    // share replaces the snapshot read by the update thread with the current one
+    pub fn share(&self, token: &Mutex<egg_mode::Token<'static>>, snapshot: &Mutex<Snapshot>) {
+        let current = self.snapshot(token);
+        match snapshot.lock() {
+            Ok(mut guard) => *guard = current,
+            Err(poisoned) => *poisoned.into_inner() = current,
+        }
+    }
+
     pub fn sync(&self) -> Result<(), ConfigError> {
+        self.store_account();
+        let toml = try!(self.seal());
         let mut file = try!(File::create(&self.filename));
-        try!(file.write_all(toml::encode_str(&self.toml).as_ref()));
+        try!(file.write_all(toml::encode_str(&toml).as_ref()));
         try!(file.sync_all());
         info!("config is synchronized");
         Ok(())
     }
+
+    // seal moves access keys into the unlocked store and returns toml without them
+    fn seal(&self) -> Result<Toml, ConfigError> {
+        let store = self.store.borrow();
+        let store = match *store {
+            Some(ref store) => store,
+            None => return Ok(self.toml.clone()),
+        };
+        let mut toml = self.toml.clone();
+        // the consumer key pair of config is kept in the store and used when config has none
+        if let Some(ref mut api) = toml.api {
+            if let (Some(key), Some(secret)) = (api.consumer_key.clone(), api.consumer_secret.clone()) {
+                store.set_consumer(key, secret);
+                api.consumer_key = None;
+                api.consumer_secret = None;
+            }
+        }
+        if let Some(ref accounts) = *toml.accounts.borrow() {
+            for account in accounts {
+                store.set(
+                    &account.name,
+                    account.access_key.key.replace(String::new()),
+                    account.access_key.secret.replace(String::new()),
+                );
+            }
+        }
+        toml.access_key.key.replace(String::new());
+        toml.access_key.secret.replace(String::new());
+        try!(store.save());
+        Ok(toml)
+    }
+
+    // unlock fills access keys from store and keeps store to seal them on sync
+    pub fn unlock(&self, store: ::secrets::Store) {
+        if let Some(ref mut accounts) = *self.toml.accounts.borrow_mut() {
+            for account in accounts.iter_mut() {
+                if let Some(pair) = store.get(&account.name) {
+                    account.access_key.key.replace(pair.key);
+                    account.access_key.secret.replace(pair.secret);
+                }
+            }
+        }
+        if let Some(pair) = store.get(&self.account()) {
+            self.set_access_key(pair.key, pair.secret);
+        }
+        *self.store.borrow_mut() = Some(store);
+    }
+
+    // migrate_secrets moves plaintext access keys into a new encrypted store
+    pub fn migrate_secrets(&self, store: ::secrets::Store, key_file: Option<String>) -> Result<(), ConfigError> {
+        *self.toml.secrets.borrow_mut() = Some(Secrets {
+            filename: store.filename().to_owned(),
+            key_file: key_file,
+        });
+        *self.store.borrow_mut() = Some(store);
+        self.sync()
+    }
+
+    // consumer returns the consumer key pair,
+    // the precedence is environment variables, config, the store and the built-in key pair
+    pub fn consumer(&self) -> (String, String) {
+        if let (Ok(key), Ok(secret)) = (env::var(::api::ENV_CONSUMER_KEY), env::var(::api::ENV_CONSUMER_SECRET)) {
+            return (key, secret);
+        }
+        if let Some(ref api) = self.toml.api {
+            if let (&Some(ref key), &Some(ref secret)) = (&api.consumer_key, &api.consumer_secret) {
+                return (key.clone(), secret.clone());
+            }
+        }
+        if let Some(ref store) = *self.store.borrow() {
+            if let Some(pair) = store.consumer() {
+                return (pair.key, pair.secret);
+            }
+        }
+        (::vars::CONSUMER_KEY.to_owned(), ::vars::CONSUMER_KEY_SECRET.to_owned())
+    }
+
+    pub fn rest_base_url(&self) -> String {
+        ::api::rest_base_url(self.toml.api.as_ref().and_then(|api| api.rest_base_url.clone()))
+    }
+
+    pub fn stream_base_url(&self) -> String {
+        ::api::stream_base_url(self.toml.api.as_ref().and_then(|api| api.stream_base_url.clone()))
+    }
+
+    pub fn upload_base_url(&self) -> String {
+        ::api::upload_base_url(self.toml.api.as_ref().and_then(|api| api.upload_base_url.clone()))
+    }
+
+    pub fn is_authorized(&self) -> bool {
+        !self.toml.access_key.key.borrow().is_empty() && !self.toml.access_key.secret.borrow().is_empty()
+    }
+
+    pub fn set_access_key(&self, key: String, secret: String) {
+        *self.toml.access_key.key.borrow_mut() = key;
+        *self.toml.access_key.secret.borrow_mut() = secret;
+    }
+
+    // account returns the name of the active account
+    pub fn account(&self) -> String {
+        match *self.toml.account.borrow() {
+            Some(ref name) => name.clone(),
+            None => DEFAULT_ACCOUNT.to_owned(),
+        }
+    }
+
+    pub fn account_names(&self) -> Vec<String> {
+        self.store_account();
+        match *self.toml.accounts.borrow() {
+            Some(ref accounts) => accounts.iter().map(|account| account.name.clone()).collect(),
+            None => vec![self.account()],
+        }
+    }
+
+    // store_account copies the active account into the account profiles
+    pub fn store_account(&self) {
+        let current = Account {
+            name: self.account(),
+            access_key: self.toml.access_key.clone(),
+            home_timeline: self.toml.home_timeline.clone(),
+            mentions_timeline: Some(self.toml.mentions_timeline.clone()),
+            likes_timeline: Some(self.toml.likes_timeline.clone()),
+            direct_messages: Some(self.toml.direct_messages.clone()),
+            lists: self.toml.lists.borrow().clone(),
+            searches: self.toml.searches.borrow().clone(),
+        };
+        let mut accounts = self.toml.accounts.borrow_mut();
+        let accounts = accounts.get_or_insert(Vec::new());
+        match accounts.iter().position(|account| account.name == current.name) {
+            Some(index) => accounts[index] = current,
+            None => accounts.push(current),
+        }
+    }
+
+    // add_account appends a new account profile without activating it
+    pub fn add_account(&self, name: String, key: String, secret: String) -> Result<(), ConfigError> {
+        if self.account_names().contains(&name) {
+            return Err(ConfigError::String(format!("account {} already exists", name)));
+        }
+        let account = Account {
+            name: name,
+            access_key: AccessKey {
+                key: RefCell::new(key),
+                secret: RefCell::new(secret),
+            },
+            home_timeline: Timeline::new(self.toml.home_timeline.limits.get()),
+            mentions_timeline: Some(Timeline::new(self.toml.mentions_timeline.limits.get())),
+            likes_timeline: Some(Timeline::new(self.toml.likes_timeline.limits.get())),
+            direct_messages: Some(Timeline::new(self.toml.direct_messages.limits.get())),
+            lists: None,
+            searches: None,
+        };
+        self.toml.accounts.borrow_mut().get_or_insert(Vec::new()).push(account);
+        Ok(())
+    }
+
+    // retention returns the number of rows of timeline kept in memory, the cache keeps only limits rows
+    pub fn retention(&self, timeline: &Timeline) -> usize {
+        cmp::max(timeline.limits.get(), self.toml.general.history)
+    }
+
+    // lists returns lists of the active account
+    pub fn lists(&self) -> Vec<List> {
+        self.toml.lists.borrow().clone().unwrap_or(Vec::new())
+    }
+
+    // set_lists replaces lists of the active account by fetched ids and names, cursors of known lists are kept
+    pub fn set_lists(&self, lists: Vec<(u64, String)>) {
+        let known = self.lists();
+        let lists = lists
+            .into_iter()
+            .map(|(id, full_name)| {
+                let timeline = match known.iter().find(|list| list.id == id) {
+                    Some(list) => list.timeline.clone(),
+                    None => Timeline::new(self.toml.home_timeline.limits.get()),
+                };
+                List {
+                    id: id,
+                    full_name: full_name,
+                    timeline: timeline,
+                }
+            })
+            .collect();
+        *self.toml.lists.borrow_mut() = Some(lists);
+    }
+
+    // searches returns saved searches of the active account
+    pub fn searches(&self) -> Vec<Search> {
+        self.toml.searches.borrow().clone().unwrap_or(Vec::new())
+    }
+
+    // add_search saves query with a new cursor, it returns false when query is already saved
+    pub fn add_search(&self, query: &str) -> bool {
+        let mut searches = self.toml.searches.borrow_mut();
+        let searches = searches.get_or_insert(Vec::new());
+        if searches.iter().any(|search| search.query == query) {
+            return false;
+        }
+        searches.push(Search {
+            query: query.to_owned(),
+            timeline: Timeline::new(self.toml.home_timeline.limits.get()),
+        });
+        true
+    }
+
+    pub fn remove_search(&self, query: &str) {
+        if let Some(ref mut searches) = *self.toml.searches.borrow_mut() {
+            searches.retain(|search| search.query != query);
+        }
+    }
+
+    // muted returns the mute list
+    pub fn muted(&self) -> Vec<String> {
+        self.toml.muted.borrow().clone().unwrap_or(Vec::new())
+    }
+
+    // mute adds a screen name or a prefixed user id to the mute list, it returns false when it is already muted
+    pub fn mute(&self, entry: &str) -> bool {
+        let entry = ::mute::normalize(entry);
+        let mut muted = self.toml.muted.borrow_mut();
+        let muted = muted.get_or_insert(Vec::new());
+        if entry.is_empty() || muted.iter().any(|known| known.to_lowercase() == entry.to_lowercase()) {
+            return false;
+        }
+        muted.push(entry);
+        true
+    }
+
+    // set_muted replaces the mute list, empty and duplicated entries are dropped
+    pub fn set_muted(&self, entries: Vec<String>) {
+        *self.toml.muted.borrow_mut() = Some(Vec::new());
+        for entry in entries {
+            self.mute(&entry);
+        }
+    }
+
+    // mute_rules returns mute rules which are not expired
+    pub fn mute_rules(&self) -> Vec<MuteRule> {
+        let mut rules = self.toml.mute_rules.borrow_mut();
+        if let Some(ref mut rules) = *rules {
+            rules.retain(|rule| !rule.is_expired());
+        }
+        rules.clone().unwrap_or(Vec::new())
+    }
+
+    pub fn add_mute_rule(&self, rule: MuteRule) {
+        self.toml.mute_rules.borrow_mut().get_or_insert(Vec::new()).push(rule);
+    }
+
+    pub fn remove_mute_rule(&self, index: usize) {
+        if let Some(ref mut rules) = *self.toml.mute_rules.borrow_mut() {
+            if index < rules.len() {
+                rules.remove(index);
+            }
+        }
+    }
+
+    // switch_account stores the active account and activates the account named name
+    pub fn switch_account(&self, name: &str) -> Result<(), ConfigError> {
+        self.store_account();
+        let account = match *self.toml.accounts.borrow() {
+            Some(ref accounts) => {
+                match accounts.iter().find(|account| account.name == name) {
+                    Some(account) => account.clone(),
+                    None => return Err(ConfigError::String(format!("account {} is not found", name))),
+                }
+            },
+            None => return Err(ConfigError::String(format!("account {} is not found", name))),
+        };
+        self.set_access_key(
+            account.access_key.key.borrow().clone(),
+            account.access_key.secret.borrow().clone(),
+        );
+        self.toml.home_timeline.set(&account.home_timeline);
+        match account.mentions_timeline {
+            Some(ref mentions_timeline) => self.toml.mentions_timeline.set(mentions_timeline),
+            None => {
+                self.toml.mentions_timeline.set(&Timeline::new(self.toml.mentions_timeline.limits.get()))
+            },
+        }
+        match account.likes_timeline {
+            Some(ref likes_timeline) => self.toml.likes_timeline.set(likes_timeline),
+            None => self.toml.likes_timeline.set(&Timeline::new(self.toml.likes_timeline.limits.get())),
+        }
+        match account.direct_messages {
+            Some(ref direct_messages) => self.toml.direct_messages.set(direct_messages),
+            None => self.toml.direct_messages.set(&Timeline::new(self.toml.direct_messages.limits.get())),
+        }
+        *self.toml.lists.borrow_mut() = account.lists.clone();
+        *self.toml.searches.borrow_mut() = account.searches.clone();
+        *self.toml.account.borrow_mut() = Some(account.name);
+        info!("switched account to {}", name);
+        Ok(())
+    }
+}
+
+// merge overwrites base by value recursively in tables
+fn merge(base: &mut toml::Value, value: toml::Value) {
+    match (base, value) {
+        (&mut toml::Value::Table(ref mut base), toml::Value::Table(value)) => {
+            for (key, value) in value {
+                match base.get_mut(&key) {
+                    Some(base) => {
+                        merge(base, value);
+                        continue;
+                    },
+                    None => (),
+                }
+                base.insert(key, value);
+            }
+        },
+        (base, value) => *base = value,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    // config returns Config read from body of a temporary config file
+    pub fn config(name: &str, body: &str) -> Config {
+        let filename = env::temp_dir().join(format!("rustytwit-test-{}.toml", name));
+        File::create(&filename).unwrap().write_all(body.as_bytes()).unwrap();
+        let config = Config::new(&filename).unwrap();
+        fs::remove_file(&filename).unwrap();
+        config
+    }
+
+    fn pair(key: &str, secret: &str) -> (String, String) {
+        (key.to_owned(), secret.to_owned())
+    }
+
+    #[test]
+    fn consumer_precedence() {
+        env::remove_var(::api::ENV_CONSUMER_KEY);
+        env::remove_var(::api::ENV_CONSUMER_SECRET);
+        let builtin = pair(::vars::CONSUMER_KEY, ::vars::CONSUMER_KEY_SECRET);
+
+        let plain = config("consumer-plain", "");
+        assert_eq!(plain.consumer(), builtin);
+
+        // the store is used when config has no consumer key pair
+        let filename = env::temp_dir().join("rustytwit-test-consumer-store.json");
+        let store = ::secrets::Store::create(&filename, "passphrase").unwrap();
+        store.set_consumer("stored key".to_owned(), "stored secret".to_owned());
+        *plain.store.borrow_mut() = Some(store.clone());
+        assert_eq!(plain.consumer(), pair("stored key", "stored secret"));
+
+        // a pair in config takes precedence over the store, a half pair is ignored
+        let half = config("consumer-half", "[api]\nconsumer_key = \"config key\"\n");
+        assert_eq!(half.consumer(), builtin);
+        let configured = config(
+            "consumer-configured",
+            "[api]\nconsumer_key = \"config key\"\nconsumer_secret = \"config secret\"\n",
+        );
+        *configured.store.borrow_mut() = Some(store);
+        assert_eq!(configured.consumer(), pair("config key", "config secret"));
+
+        // environment variables take precedence over all
+        env::set_var(::api::ENV_CONSUMER_KEY, "env key");
+        assert_eq!(configured.consumer(), pair("config key", "config secret"));
+        env::set_var(::api::ENV_CONSUMER_SECRET, "env secret");
+        assert_eq!(configured.consumer(), pair("env key", "env secret"));
+        assert_eq!(plain.consumer(), pair("env key", "env secret"));
+        env::remove_var(::api::ENV_CONSUMER_KEY);
+        env::remove_var(::api::ENV_CONSUMER_SECRET);
+    }
 }
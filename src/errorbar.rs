@@ -0,0 +1,90 @@
+use gtk;
+use gtk::prelude::*;
+
+// RESPONSE_REAUTHORIZE is response id of re-authorize button
+pub const RESPONSE_REAUTHORIZE: i32 = 1;
+
+// ErrorBar reports errors of twitter API in the window instead of exiting
+#[derive(Clone)]
+pub struct ErrorBar {
+    pub infobar: gtk::InfoBar,
+    label: gtk::Label,
+    button_reauthorize: gtk::Button,
+}
+
+impl ErrorBar {
+    pub fn new() -> ErrorBar {
+        let infobar = gtk::InfoBar::new();
+        infobar.set_show_close_button(true);
+
+        let label = gtk::Label::new(None);
+        label.set_line_wrap(true);
+        label.set_xalign(0.0);
+        if let Some(content_area) = infobar.get_content_area() {
+            content_area.add(&label);
+        }
+
+        let button_reauthorize = gtk::Button::new_with_label("Re-authorize");
+        infobar.add_action_widget(&button_reauthorize, RESPONSE_REAUTHORIZE);
+
+        // close button only hides infobar
+        infobar.connect_response(|infobar, response| if response == gtk::ResponseType::Close.into() {
+            infobar.hide();
+        });
+
+        ErrorBar {
+            infobar: infobar,
+            label: label,
+            button_reauthorize: button_reauthorize,
+        }
+    }
+
+    pub fn show_error(&self, err: &::api::ApiError) {
+        if is_unauthorized(err) {
+            self.infobar.set_message_type(gtk::MessageType::Error);
+            self.label.set_text("Authentication failed, the access token may be revoked.");
+            self.button_reauthorize.show();
+            self.infobar.show();
+            self.label.show();
+        } else {
+            self.show_warning(&format!("Failed to update timeline, it will be retried: {}", describe(err)));
+        }
+    }
+
+    // show_warning reports an error which is not of twitter API
+    pub fn show_warning(&self, message: &str) {
+        self.infobar.set_message_type(gtk::MessageType::Warning);
+        self.label.set_text(message);
+        self.button_reauthorize.hide();
+        self.infobar.show();
+        self.label.show();
+    }
+
+    // show_render_error reports rows of a timeline which are not rendered, other rows are kept shown
+    pub fn show_render_error(&self, err: &::timeline::home::TimelineError) {
+        self.show_warning(&format!("Failed to render the timeline: {:?}", err));
+    }
+
+    pub fn hide(&self) {
+        self.infobar.hide();
+    }
+}
+
+// is_unauthorized returns true when err is caused by invalid or revoked credentials
+pub fn is_unauthorized(err: &::api::ApiError) -> bool {
+    match *err {
+        ::api::ApiError::Status(401, _) => true,
+        _ => false,
+    }
+}
+
+// describe returns the message of err shown to the user
+pub fn describe(err: &::api::ApiError) -> String {
+    match *err {
+        ::api::ApiError::Status(code, _) => format!("twitter API returned status {}", code),
+        ::api::ApiError::Io(ref err) => format!("{}", err),
+        ::api::ApiError::Hyper(ref err) => format!("{}", err),
+        ::api::ApiError::Json(ref err) => format!("{}", err),
+        ::api::ApiError::InvalidResponse(ref err) => err.clone(),
+    }
+}
@@ -2,6 +2,7 @@ extern crate chrono;
 extern crate crypto;
 extern crate egg_mode;
 extern crate getopts;
+extern crate gdk;
 extern crate gdk_pixbuf;
 extern crate gdk_pixbuf_sys;
 extern crate glib;
@@ -9,6 +10,7 @@ extern crate gtk;
 extern crate gtk_sys;
 extern crate hyper;
 extern crate hyper_native_tls;
+extern crate rand;
 extern crate regex;
 extern crate rustc_serialize;
 extern crate toml;
@@ -18,7 +20,7 @@ extern crate log;
 extern crate env_logger;
 
 use getopts::Options;
-use gtk::{Box, Image, Label, ListBox, Paned, Revealer};
+use gtk::{Box, Image, Label, ListBox, ListBoxRow, Paned};
 use gtk::{ScrolledWindow, Window, WindowType};
 use gtk::{ToolButton, ToolItem};
 use gtk::Orientation;
@@ -27,20 +29,30 @@ use gtk::Switch;
 // import gtk
 use gtk::prelude::*;
 
-use std::{env, fs};
-use std::ops::DerefMut;
+use std::{cmp, env, fs};
+use std::ops::{Deref, DerefMut};
+use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::channel;
 use std::thread;
 use std::time;
 
 // module import
+mod api;
 mod auth;
 mod vars;
 mod config;
 mod timeline;
 mod utils;
 mod cache;
+mod compose;
+mod errorbar;
+mod media;
+mod mute;
+mod outbox;
+mod preferences;
+mod secrets;
+mod undobar;
 
 pub fn main() {
     // parse flags
@@ -82,24 +94,68 @@ pub fn main() {
 
     let cache_dir = home_dir.clone().join(vars::CACHE_DIR).join("rustytwit");
     let cache_image_dir = cache_dir.clone().join("image");
+    let cache_media_dir = cache_dir.clone().join(vars::CACHE_MEDIA_DIR);
     let config_dir = home_dir.clone().join(vars::CONFIG_DIR).join("rustytwit");
     {
         fs::create_dir_all(cache_dir.clone()).ok();
         fs::create_dir_all(cache_image_dir.clone()).ok();
+        fs::create_dir_all(cache_media_dir.clone()).ok();
         fs::create_dir_all(config_dir.clone()).ok();
     }
     let filename = config_dir.clone().join(vars::CONFIG);
 
-    let mut config = match config::Config::new(&filename) {
-        Ok(config) => Arc::new(config),
+    let config = match config::Config::new(&filename) {
+        Ok(config) => config,
         Err(err) => {
             error!("{:?}", err);
             panic!("{:?}", err)
         },
     };
 
+    // gui initialization
+    if gtk::init().is_err() {
+        println!("Failed to initialize GTK.");
+        return;
+    }
+
+    // unlock the credential store
+    let secrets = config.toml.secrets.borrow().clone();
+    if let Some(secrets) = secrets {
+        let store = loop {
+            let passphrase = match secrets.key_file {
+                Some(ref key_file) => secrets::read_key_file(key_file),
+                None => secrets::passphrase_dialog(None, false),
+            };
+            let result = match passphrase {
+                Ok(passphrase) => secrets::Store::open(&secrets.filename, &passphrase),
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(store) => break store,
+                // retry when the passphrase is wrong
+                Err(secrets::SecretsError::Decrypt) if secrets.key_file.is_none() => continue,
+                // access keys are not available without the store, rustytwit quits
+                Err(secrets::SecretsError::Cancel) => {
+                    println!("Unlocking the credential store was cancelled.");
+                    return;
+                },
+                Err(err) => {
+                    error!("{:?}", err);
+                    println!("Failed to unlock the credential store: {:?}", err);
+                    return;
+                },
+            }
+        };
+        config.unlock(store);
+    }
+    let config = Arc::new(config);
+
+    // tweets of muted users are hidden from the first rendering of caches
+    timeline::utils::set_muted(config.muted());
+    timeline::utils::set_mute_rules(config.mute_rules());
+
     // load timeline cache
-    let cache_home_timeline = cache_dir.clone().join("home_timeline.json");
+    let cache_home_timeline = cache::path(&cache_dir, vars::CACHE_HOME, &config.account());
     let home_timeline: Vec<timeline::home::TimelineRow> = match cache::load(cache_home_timeline) {
         Ok(home) => home,
         Err(err) => {
@@ -109,36 +165,70 @@ pub fn main() {
     };
     let home_timeline = Arc::new(Mutex::new(home_timeline));
 
-    // variable initialization
-    let consumer: egg_mode::KeyPair = egg_mode::KeyPair::new(::vars::CONSUMER_KEY, ::vars::CONSUMER_KEY_SECRET);
-
-    // if config.toml.access_key.key == "" || config.toml.access_key.secret == "" {
-    // let consumer = egg_mode::KeyPair::new(::vars::CONSUMER_KEY, ::vars::CONSUMER_KEY_SECRET);
-    // let token = match auth::authorize(consumer) {
-    //     Ok(access) => access,
-    //     Err(err) => {
-    //         error!("{:?}", err);
-    //         panic!("{:?}", err)
-    //     },
-    // };
-    // Arc::make_mut(&mut config).toml.access_key.key = token.access.access.key.into_owned();
-    // Arc::make_mut(&mut config).toml.access_key.secret = token.access.access.secret.into_owned();
-    // }
-    let access: egg_mode::KeyPair = egg_mode::KeyPair::new(
-        config.toml.access_key.key.clone(),
-        config.toml.access_key.secret.clone(),
-    );
+    let cache_mentions_timeline = cache::path(&cache_dir, vars::CACHE_MENTIONS, &config.account());
+    let mentions_timeline: Vec<timeline::home::TimelineRow> = match cache::load(cache_mentions_timeline) {
+        Ok(mentions) => mentions,
+        Err(err) => {
+            error!("{:?}", err);
+            panic!("{:?}", err)
+        },
+    };
+    let mentions_timeline = Arc::new(Mutex::new(mentions_timeline));
 
-    let token = Arc::new(egg_mode::Token::Access {
-        consumer: consumer,
-        access: access,
-    });
+    let cache_likes_timeline = cache::path(&cache_dir, vars::CACHE_LIKES, &config.account());
+    let likes_timeline: Vec<timeline::home::TimelineRow> = match cache::load(cache_likes_timeline) {
+        Ok(likes) => likes,
+        Err(err) => {
+            error!("{:?}", err);
+            panic!("{:?}", err)
+        },
+    };
 
-    // gui initialization
-    if gtk::init().is_err() {
-        println!("Failed to initialize GTK.");
-        return;
+    let cache_direct_messages = cache::path(&cache_dir, vars::CACHE_DIRECT_MESSAGES, &config.account());
+    let direct_messages: Vec<timeline::dm::Conversation> = match cache::load(cache_direct_messages) {
+        Ok(conversations) => conversations,
+        Err(err) => {
+            error!("{:?}", err);
+            panic!("{:?}", err)
+        },
+    };
+
+    let cache_outbox = cache::path(&cache_dir, vars::CACHE_OUTBOX, &config.account());
+    let outbox: Vec<outbox::Entry> = match cache::load(cache_outbox) {
+        Ok(outbox) => outbox,
+        Err(err) => {
+            error!("{:?}", err);
+            panic!("{:?}", err)
+        },
+    };
+
+    // authorize at first launch
+    if !config.is_authorized() {
+        // rustytwit quits without an access token, errors of the authorization are retried in the dialog
+        let token = match auth::authorize_dialog(None, &config) {
+            Ok((token, _)) => token,
+            Err(auth::AuthError::Cancel) => {
+                println!("Authorization was cancelled.");
+                return;
+            },
+            Err(err) => {
+                error!("{:?}", err);
+                println!("Authorization failed: {:?}", err);
+                return;
+            },
+        };
+        match auth::save_token(&config, &token) {
+            Ok(_) => (),
+            Err(err) => {
+                error!("{:?}", err);
+                println!("Failed to save the access token: {:?}", err);
+                return;
+            },
+        }
     }
+    let token = Arc::new(Mutex::new(auth::token(&config)));
+    // snapshot is shared with the update thread instead of config
+    let snapshot = Arc::new(Mutex::new(config.snapshot(&token)));
 
     let window = Window::new(WindowType::Toplevel);
     window.set_title("rustytwit");
@@ -158,61 +248,291 @@ pub fn main() {
         },
     }
 
+    // errorbar setup
+    let errorbar = errorbar::ErrorBar::new();
+
+    // undobar setup, deleting a tweet is deferred by it
+    let undobar = undobar::UndoBar::new();
+
+    // stack setup, the visible timeline is switched by side_listbox
+    let stack = gtk::Stack::new();
+
     vbox.pack_start(&toolbar, false, false, 0);
-    vbox.pack_start(&scrolled_window, true, true, 0);
+    vbox.pack_start(&errorbar.infobar, false, false, 0);
+    vbox.pack_start(&undobar.infobar, false, false, 0);
+    vbox.pack_start(&stack, true, true, 0);
 
     // listbox setup
     let listbox = ListBox::new();
     scrolled_window.add(&listbox);
+    stack.add_named(&scrolled_window, "home");
+
+    // mentions_listbox setup
+    let mentions_scrolled_window = ScrolledWindow::new(None, None);
+    let mentions_listbox = ListBox::new();
+    mentions_scrolled_window.add(&mentions_listbox);
+    stack.add_named(&mentions_scrolled_window, "mentions");
+
+    // likes_view setup
+    let likes_view = timeline::likes::LikesView::new(likes_timeline, config.clone(), token.clone(), errorbar.clone());
+    likes_view.render();
+    stack.add_named(&likes_view.container, "likes");
+
+    // dm_view setup, dm_label is the side pane label which shows unread messages
+    let dm_label = Label::new(Some("Direct Messages"));
+    let dm_view = timeline::dm::DirectMessagesView::new(
+        direct_messages,
+        dm_label.clone(),
+        config.clone(),
+        token.clone(),
+        errorbar.clone(),
+    );
+    dm_view.render();
+    stack.add_named(&dm_view.container, "direct_messages");
+
+    // outbox_view setup, outbox_label is the side pane label which shows the number of queued tweets
+    let outbox_label = Label::new(Some("Outbox"));
+    let outbox_view = outbox::OutboxView::new(
+        outbox,
+        outbox_label.clone(),
+        config.clone(),
+        token.clone(),
+        cache_dir.clone(),
+    );
+    outbox_view.render();
+    stack.add_named(&outbox_view.container, "outbox");
 
     // side_listbox setup
     let side_listbox = ListBox::new();
 
-    // let timeline_label = Label::new(Some("<b>Timeline</b>"));
-    // let home_label = Label::new(Some("Home"));
-    // let mention_label = Label::new(Some("Mention"));
-    // let favorite_label = Label::new(Some("Favorite"));
-    // let dm_label = Label::new(Some("Direct Messages"));
-    // let list_label = Label::new(Some("<b>List</b>"));
-
-    // timeline_label.set_padding(16, 6);
-    // home_label.set_padding(16, 6);
-    // mention_label.set_padding(16, 6);
-    // favorite_label.set_padding(16, 6);
-    // dm_label.set_padding(16, 6);
-    // list_label.set_padding(16, 6);
-
-    // timeline_label.set_xalign(0.0);
-    // list_label.set_xalign(0.0);
-
-    // timeline_label.set_use_markup(true);
-    // list_label.set_use_markup(true);
-
-    // let timeline = ListBoxRow::new();
-    // let home = gtk::EventBox::new();
-    // let mention = ListBoxRow::new();
-    // let favorite = ListBoxRow::new();
-    // let dm = ListBoxRow::new();
-    // let list = ListBoxRow::new();
-
-    // timeline.add(&timeline_label);
-    // home.add(&home_label);
-    // mention.add(&mention_label);
-    // favorite.add(&favorite_label);
-    // dm.add(&dm_label);
-    // list.add(&list_label);
-
-    // timeline.set_selectable(false);
-    // list.set_selectable(false);
-    // timeline.set_can_focus(false);
-    // list.set_can_focus(false);
-
-    // left_list_box.insert(&timeline, -1);
-    // left_list_box.insert(&home, -1);
-    // left_list_box.insert(&mention, -1);
-    // left_list_box.insert(&favorite, -1);
-    // left_list_box.insert(&dm, -1);
-    // left_list_box.insert(&list, -1);
+    // account switcher setup
+    let account_combobox = gtk::ComboBoxText::new();
+    let account_add_button = gtk::Button::new_from_icon_name("list-add", 1);
+    {
+        for (index, name) in config.account_names().iter().enumerate() {
+            account_combobox.append_text(name);
+            if *name == config.account() {
+                account_combobox.set_active(index as i32);
+            }
+        }
+
+        let account_box = Box::new(Orientation::Horizontal, 2);
+        account_box.set_border_width(6);
+        account_box.pack_start(&account_combobox, true, true, 0);
+        account_box.pack_start(&account_add_button, false, false, 0);
+
+        let account_row = gtk::ListBoxRow::new();
+        account_row.add(&account_box);
+        account_row.set_selectable(false);
+        account_row.set_can_focus(false);
+        side_listbox.insert(&account_row, -1);
+    }
+
+    let timeline_label = Label::new(Some("<b>Timeline</b>"));
+    let home_label = Label::new(Some("Home"));
+    let mention_label = Label::new(Some("Mention"));
+    let favorite_label = Label::new(Some("Favorite"));
+
+    timeline_label.set_padding(16, 6);
+    home_label.set_padding(16, 6);
+    mention_label.set_padding(16, 6);
+    favorite_label.set_padding(16, 6);
+    dm_label.set_padding(16, 6);
+    outbox_label.set_padding(16, 6);
+
+    timeline_label.set_xalign(0.0);
+    home_label.set_xalign(0.0);
+    mention_label.set_xalign(0.0);
+    favorite_label.set_xalign(0.0);
+    dm_label.set_xalign(0.0);
+    outbox_label.set_xalign(0.0);
+
+    timeline_label.set_use_markup(true);
+
+    let timeline_row = ListBoxRow::new();
+    let home_row = ListBoxRow::new();
+    let mention_row = ListBoxRow::new();
+    let favorite_row = ListBoxRow::new();
+    let dm_row = ListBoxRow::new();
+    let outbox_row = ListBoxRow::new();
+
+    // the name of row is the name of stack child
+    home_row.set_name("home");
+    mention_row.set_name("mentions");
+    favorite_row.set_name("likes");
+    dm_row.set_name("direct_messages");
+    outbox_row.set_name("outbox");
+
+    timeline_row.add(&timeline_label);
+    home_row.add(&home_label);
+    mention_row.add(&mention_label);
+    favorite_row.add(&favorite_label);
+    dm_row.add(&dm_label);
+    outbox_row.add(&outbox_label);
+
+    timeline_row.set_selectable(false);
+    timeline_row.set_can_focus(false);
+
+    side_listbox.insert(&timeline_row, -1);
+    side_listbox.insert(&home_row, -1);
+    side_listbox.insert(&mention_row, -1);
+    side_listbox.insert(&favorite_row, -1);
+    side_listbox.insert(&dm_row, -1);
+    side_listbox.insert(&outbox_row, -1);
+
+    // search_view setup, saved searches follow the Search header
+    let search_view = timeline::search::SearchView::new(
+        &side_listbox,
+        &stack,
+        config.clone(),
+        token.clone(),
+        errorbar.clone(),
+        cache_dir.clone(),
+    );
+    search_view.load();
+    {
+        let search_view = search_view.clone();
+
+        timeline::utils::register_link_handler("hashtag", move |hashtag| {
+            search_view.open(&format!("#{}", hashtag));
+        });
+    }
+
+    // lists_view setup, lists follow the List header at the bottom of side_listbox
+    let lists_view = timeline::lists::ListsView::new(
+        &side_listbox,
+        &stack,
+        config.clone(),
+        token.clone(),
+        errorbar.clone(),
+        cache_dir.clone(),
+    );
+    lists_view.load();
+    side_listbox.select_row(Some(&home_row));
+
+    // event definition, when load missing tweets link of home is clicked
+    {
+        let config = config.clone();
+        let token = token.clone();
+        let errorbar = errorbar.clone();
+        let listbox = listbox.clone();
+        let home = home_timeline.clone();
+
+        timeline::utils::register_link_handler("gap", move |id| {
+            let id = match u64::from_str_radix(id, 10) {
+                Ok(id) => id,
+                Err(err) => {
+                    error!("{}", err);
+                    return;
+                },
+            };
+            let client = api::client(&config, &token);
+            let limits = config.toml.home_timeline.limits.get();
+            let count = timeline::utils::page_size(limits);
+            let (result, rows) = {
+                let mut guard = match home.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let timeline = guard.deref_mut();
+                let result = timeline::home::backfill(&client, timeline, id, count, limits);
+                timeline::home::fixup_home(timeline, config.retention(&config.toml.home_timeline));
+                (result, timeline.clone())
+            };
+            match result {
+                Ok(_) => errorbar.hide(),
+                Err(err) => {
+                    error!("{:?}", err);
+                    errorbar.show_error(&err);
+                },
+            }
+            match timeline::home::update_home(&listbox, &rows, false, false) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+        });
+    }
+
+    // thread_view setup, it is opened by conversation links of expanded rows
+    let thread_view = timeline::thread::ThreadView::new();
+    stack.add_named(&thread_view.container, "thread");
+    {
+        let config = config.clone();
+        let token = token.clone();
+        let errorbar = errorbar.clone();
+        let stack = stack.clone();
+        let side_listbox = side_listbox.clone();
+        let home = home_timeline.clone();
+        let mentions = mentions_timeline.clone();
+        let likes = likes_view.timeline.clone();
+
+        timeline::utils::register_link_handler("thread", move |id| {
+            let id = match u64::from_str_radix(id, 10) {
+                Ok(id) => id,
+                Err(err) => {
+                    error!("{}", err);
+                    return;
+                },
+            };
+            // cached tweets are walked before requesting the API
+            let mut known: Vec<timeline::home::TimelineRow> = Vec::new();
+            for cached in &[&home, &mentions, &likes] {
+                match cached.lock() {
+                    Ok(guard) => known.extend(guard.iter().cloned()),
+                    Err(poisoned) => known.extend(poisoned.into_inner().iter().cloned()),
+                }
+            }
+            let client = api::client(&config, &token);
+            match thread_view.show(&client, id, &known) {
+                Ok(_) => errorbar.hide(),
+                Err(err) => {
+                    error!("{:?}", err);
+                    errorbar.show_error(&err);
+                    return;
+                },
+            }
+            side_listbox.unselect_all();
+            stack.set_visible_child_name("thread");
+        });
+    }
+
+    // profile_view setup, it is opened by user links in rows instead of side_listbox
+    let profile_view = timeline::profile::ProfileView::new(config.clone(), token.clone(), errorbar.clone());
+    stack.add_named(&profile_view.container, "profile");
+    {
+        let config = config.clone();
+        let token = token.clone();
+        let errorbar = errorbar.clone();
+        let stack = stack.clone();
+        let side_listbox = side_listbox.clone();
+
+        timeline::utils::register_link_handler("user", move |screen_name| {
+            let client = api::client(&config, &token);
+            match profile_view.show(&client, screen_name, vars::PAGE_SIZE as i32) {
+                Ok(_) => errorbar.hide(),
+                Err(err) => {
+                    error!("{:?}", err);
+                    errorbar.show_error(&err);
+                    return;
+                },
+            }
+            side_listbox.unselect_all();
+            stack.set_visible_child_name("profile");
+        });
+    }
+
+    // event definition, when side_listbox row is selected
+    {
+        let stack = stack.clone();
+
+        side_listbox.connect_row_selected(move |_, listboxrow| {
+            let name = match listboxrow.clone().and_then(|listboxrow| listboxrow.get_name()) {
+                Some(name) => name,
+                None => return,
+            };
+            stack.set_visible_child_name(&name);
+        });
+    }
 
     // paned setup
     let paned = Paned::new(Orientation::Horizontal);
@@ -225,11 +545,25 @@ pub fn main() {
     let refresh_button = ToolButton::new(Some(&refresh_button_icon), Some(refresh_button_label));
     toolbar.insert(&refresh_button, 0);
 
+    let compose_button_icon = Image::new_from_icon_name("document-edit", 0);
+    let compose_button_label = "compose";
+    let compose_button = ToolButton::new(Some(&compose_button_icon), Some(compose_button_label));
+    compose_button.set_tooltip_text(Some("Compose a tweet (Ctrl+N)"));
+    toolbar.insert(&compose_button, 1);
+
+    // shortcut of compose_button
+    {
+        let accel_group = gtk::AccelGroup::new();
+        window.add_accel_group(&accel_group);
+        let (key, modifier) = gtk::accelerator_parse("<Control>n");
+        compose_button.add_accelerator("clicked", &accel_group, key, modifier, gtk::ACCEL_VISIBLE);
+    }
+
     {
         let separator_spacer = gtk::SeparatorToolItem::new();
         separator_spacer.set_expand(true);
         separator_spacer.set_draw(false);
-        toolbar.insert(&separator_spacer, 1);
+        toolbar.insert(&separator_spacer, 2);
     }
 
     // definition url filter
@@ -238,6 +572,7 @@ pub fn main() {
         let listbox = listbox.clone();
         let config = config.clone();
         let home = home_timeline.clone();
+        let errorbar = errorbar.clone();
 
         let vbox = Box::new(Orientation::Vertical, 0);
 
@@ -249,12 +584,12 @@ pub fn main() {
             };
             debug!("switch_unread is {}", switch.get_active());
             let timeline = guard.deref_mut();
-            timeline::home::fixup_home(timeline, config.toml.home_timeline.limits.get());
+            timeline::home::fixup_home(timeline, config.retention(&config.toml.home_timeline));
             match timeline::home::update_home(&listbox, timeline, false, flag) {
                 Ok(_) => (),
                 Err(err) => {
                     error!("{:?}", err);
-                    panic!("{:?}", err)
+                    errorbar.show_render_error(&err);
                 },
             }
             debug!("{}", flag);
@@ -268,13 +603,13 @@ pub fn main() {
         vbox.pack_start(&label_text, true, true, 1);
         toolitem_url.add(&vbox);
     }
-    toolbar.insert(&toolitem_url, 2);
+    toolbar.insert(&toolitem_url, 3);
 
     {
         let separator_spacer = gtk::SeparatorToolItem::new();
         separator_spacer.set_expand(false);
         separator_spacer.set_draw(false);
-        toolbar.insert(&separator_spacer, 3);
+        toolbar.insert(&separator_spacer, 4);
     }
 
     // definition unread filter
@@ -283,6 +618,7 @@ pub fn main() {
         let listbox = listbox.clone();
         let config = config.clone();
         let home = home_timeline.clone();
+        let errorbar = errorbar.clone();
 
         let vbox = Box::new(Orientation::Vertical, 0);
 
@@ -294,12 +630,12 @@ pub fn main() {
             };
             debug!("switch_unread is {}", switch.get_active());
             let timeline = guard.deref_mut();
-            timeline::home::fixup_home(timeline, config.toml.home_timeline.limits.get());
+            timeline::home::fixup_home(timeline, config.retention(&config.toml.home_timeline));
             match timeline::home::update_home(&listbox, timeline, false, flag) {
                 Ok(_) => (),
                 Err(err) => {
                     error!("{:?}", err);
-                    panic!("{:?}", err)
+                    errorbar.show_render_error(&err);
                 },
             }
             return Inhibit(false);
@@ -311,235 +647,838 @@ pub fn main() {
         vbox.pack_start(&label_text, true, true, 1);
         toolitem_unread.add(&vbox);
     }
-    toolbar.insert(&toolitem_unread, 4);
+    toolbar.insert(&toolitem_unread, 5);
 
     let separator_bar = gtk::SeparatorToolItem::new();
     separator_bar.set_draw(true);
-    toolbar.insert(&separator_bar, 5);
+    toolbar.insert(&separator_bar, 6);
 
     let pref_button_icon = Image::new_from_icon_name("gtk-preferences", 1);
     let pref_button_label = "preferences";
     let pref_button = ToolButton::new(Some(&pref_button_icon), Some(pref_button_label));
-    toolbar.insert(&pref_button, 6);
+    toolbar.insert(&pref_button, 7);
 
-    // window setup
-    window.add(&paned);
-    window.show_all();
+    // remute applies the mute list and mute rules of config, and renders timelines again.
+    // config is written to the config file, stream-notification reloads the mute list from it
+    let remute = {
+        let config = config.clone();
+        let listbox = listbox.clone();
+        let home = home_timeline.clone();
+        let mentions = mentions_timeline.clone();
+        let mentions_listbox = mentions_listbox.clone();
+        let mention_label = mention_label.clone();
+        let likes_view = likes_view.clone();
+        let dm_view = dm_view.clone();
+        let lists_view = lists_view.clone();
+        let search_view = search_view.clone();
+
+        Rc::new(move || {
+            timeline::utils::set_muted(config.muted());
+            timeline::utils::set_mute_rules(config.mute_rules());
+            match config.sync() {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+            {
+                let guard = match home.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                match timeline::home::update_home(&listbox, guard.deref(), false, false) {
+                    Ok(_) => (),
+                    Err(err) => error!("{:?}", err),
+                }
+            }
+            {
+                let guard = match mentions.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                match timeline::home::update_home(&mentions_listbox, guard.deref(), false, false) {
+                    Ok(_) => (),
+                    Err(err) => error!("{:?}", err),
+                }
+                mention_label.set_text(&timeline::utils::unread_label("Mention", guard.deref()));
+            }
+            likes_view.render();
+            dm_view.render();
+            lists_view.render();
+            search_view.render();
+        })
+    };
+
+    // event definition, when mute is selected in the context menu of a row
     {
         let config = config.clone();
+        let remute = remute.clone();
+
+        timeline::utils::register_mute_handler(move |user| if config.mute(&user.screen_name) {
+            remute();
+        });
+    }
+
+    // event definition, when pref_button is clicked, the mute list and mute rules may be edited
+    {
+        let window = window.clone();
+        let config = config.clone();
+        let token = token.clone();
+
+        pref_button.connect_clicked(move |_| {
+            preferences::show(&window, config.clone(), token.clone());
+            remute();
+        });
+    }
+
+    // sent is called with the result of the compose dialog, a posted tweet is inserted into home
+    // and a queued or unfinished thread is kept in the outbox
+    let sent: Rc<Fn(compose::Sent)> = {
         let listbox = listbox.clone();
+        let config = config.clone();
+        let home = home_timeline.clone();
+        let outbox_view = outbox_view.clone();
 
-        let mut guard = match home_timeline.lock() {
-            Ok(guard) => guard,
-            Err(poisoned) => poisoned.into_inner(),
-        };
-        let mut timeline = guard.deref_mut();
-        timeline::home::fixup_home(timeline, config.toml.home_timeline.limits.get());
-        match timeline::home::update_home(&listbox, timeline, false, false) {
-            Ok(_) => (),
-            Err(err) => {
-                error!("{:?}", err);
-                panic!("{:?}", err)
+        Rc::new(move |sent| match sent {
+            compose::Sent::Posted(row) => {
+                timeline::home::insert_posted(&listbox, &home, row, config.retention(&config.toml.home_timeline))
             },
-        }
+            compose::Sent::Queued(thread, scheduled_at) => outbox_view.push(thread, scheduled_at),
+            compose::Sent::Held(thread, error) => outbox_view.hold(thread, error),
+        })
+    };
+
+    // event definition, when a tweet is posted by retry button of the outbox
+    {
+        let listbox = listbox.clone();
+        let config = config.clone();
+        let home = home_timeline.clone();
+
+        outbox_view.connect_posted(move |row| {
+            timeline::home::insert_posted(&listbox, &home, row, config.retention(&config.toml.home_timeline));
+        });
     }
 
-    // event definition, when refresh_button is clicked
+    // event definition, when edit button of the outbox is clicked, the draft is queued again when it is sent
+    {
+        let window = window.clone();
+        let config = config.clone();
+        let token = token.clone();
+        let sent = sent.clone();
+        let cache_media_dir = cache_media_dir.clone();
+
+        outbox_view.connect_edit(move |draft, scheduled_at| {
+            compose::show(
+                &window,
+                config.clone(),
+                token.clone(),
+                cache_media_dir.clone(),
+                draft,
+                scheduled_at,
+                |result| sent(result),
+            )
+        });
+    }
+
+    // event definition, when compose_button is clicked
+    {
+        let window = window.clone();
+        let config = config.clone();
+        let token = token.clone();
+        let sent = sent.clone();
+        let cache_media_dir = cache_media_dir.clone();
+
+        compose_button.connect_clicked(move |_| {
+            compose::show(
+                &window,
+                config.clone(),
+                token.clone(),
+                cache_media_dir.clone(),
+                compose::Draft::default(),
+                None,
+                |result| sent(result),
+            );
+        });
+    }
+
+    // action definition, when a button of the action bar on the expanded row is clicked
     {
+        let window = window.clone();
         let listbox = listbox.clone();
         let config = config.clone();
         let home = home_timeline.clone();
+        let mentions = mentions_timeline.clone();
+        let mentions_listbox = mentions_listbox.clone();
+        let mention_label = mention_label.clone();
+        let likes_view = likes_view.clone();
         let token = token.clone();
+        let errorbar = errorbar.clone();
+        let undobar = undobar.clone();
+        let sent = sent.clone();
+        let cache_media_dir = cache_media_dir.clone();
+
+        // render_cached renders timelines which rows of a deleted tweet are removed from or restored to
+        let render_cached = {
+            let listbox = listbox.clone();
+            let home = home.clone();
+            let mentions = mentions.clone();
+            let likes_view = likes_view.clone();
+
+            Rc::new(move || {
+                {
+                    let guard = match home.lock() {
+                        Ok(guard) => guard,
+                        Err(poisoned) => poisoned.into_inner(),
+                    };
+                    match timeline::home::update_home(&listbox, guard.deref(), false, false) {
+                        Ok(_) => (),
+                        Err(err) => error!("{:?}", err),
+                    }
+                }
+                {
+                    let guard = match mentions.lock() {
+                        Ok(guard) => guard,
+                        Err(poisoned) => poisoned.into_inner(),
+                    };
+                    match timeline::home::update_home(&mentions_listbox, guard.deref(), false, false) {
+                        Ok(_) => (),
+                        Err(err) => error!("{:?}", err),
+                    }
+                    mention_label.set_text(&timeline::utils::unread_label("Mention", guard.deref()));
+                }
+                likes_view.render();
+            })
+        };
 
-        refresh_button.connect_clicked(move |_| {
-            let home_timeline = match timeline::home::home_timeline(
-                token.as_ref(),
-                Some(config.toml.home_timeline.last_update_id.get() as u64),
-                config.toml.home_timeline.limits.get() as i32,
-            ) {
-                Ok(home_timeline) => (home_timeline),
-                Err(_) => {
-                    std::process::exit(1);
-                },
+        timeline::utils::register_action_handler(move |action, tweet| {
+            let draft = match action {
+                timeline::utils::Action::Reply => Some(compose::Draft::reply(tweet)),
+                timeline::utils::Action::Quote => Some(compose::Draft::quote(tweet)),
+                _ => None,
             };
-            match home_timeline.first() {
-                Some(status) => {
-                    config.toml.home_timeline.last_update_id.set(
-                        status.tweet.id,
-                    )
-                },
-                None => (),
+            if let Some(draft) = draft {
+                compose::show(
+                    &window,
+                    config.clone(),
+                    token.clone(),
+                    cache_media_dir.clone(),
+                    draft,
+                    None,
+                    |result| sent(result),
+                );
+                return None;
             }
-            // add tweets to home_timeline and update home_timeline
-            {
-                let mut guard = match home.lock() {
-                    Ok(guard) => guard,
-                    Err(poisoned) => poisoned.into_inner(),
+
+            // rows of the deleted tweet are removed at once, and the request is sent after the grace period
+            if action == timeline::utils::Action::Delete {
+                let caches = vec![home.clone(), mentions.clone(), likes_view.timeline.clone()];
+                let removed: Vec<Vec<(usize, timeline::home::TimelineRow)>> = caches
+                    .iter()
+                    .map(|cached| {
+                        let mut guard = match cached.lock() {
+                            Ok(guard) => guard,
+                            Err(poisoned) => poisoned.into_inner(),
+                        };
+                        timeline::actions::remove(guard.deref_mut(), tweet.id)
+                    })
+                    .collect();
+                render_cached();
+
+                let restore = {
+                    let render_cached = render_cached.clone();
+
+                    Rc::new(move || {
+                        for (cached, rows) in caches.iter().zip(removed.iter()) {
+                            let mut guard = match cached.lock() {
+                                Ok(guard) => guard,
+                                Err(poisoned) => poisoned.into_inner(),
+                            };
+                            timeline::actions::restore(guard.deref_mut(), rows);
+                        }
+                        render_cached();
+                    })
                 };
-                let mut index = 0;
-                for row in home_timeline.clone() {
-                    guard.insert(index, row.clone());
-                    index += 1;
-                }
-                let mut timeline = guard.deref_mut();
-                timeline::home::fixup_home(timeline, config.toml.home_timeline.limits.get());
+                let commit = {
+                    let config = config.clone();
+                    let token = token.clone();
+                    let errorbar = errorbar.clone();
+                    let restore = restore.clone();
+                    let id = tweet.id;
+
+                    move || {
+                        let client = api::client(&config, &token);
+                        match timeline::actions::destroy(&client, id) {
+                            Ok(_) => (),
+                            Err(err) => {
+                                error!("{:?}", err);
+                                errorbar.show_error(&err);
+                                restore();
+                            },
+                        }
+                    }
+                };
+                undobar.defer("The tweet is deleted.", commit, move || restore());
+                return None;
             }
-            match timeline::home::update_home(&listbox, &home_timeline, true, false) {
-                Ok(_) => (),
+
+            let client = api::client(&config, &token);
+            let engagement = match timeline::actions::run(&client, action, tweet.status_id()) {
+                Ok(engagement) => engagement,
                 Err(err) => {
                     error!("{:?}", err);
-                    panic!("{:?}", err)
+                    errorbar.show_error(&err);
+                    return None;
                 },
+            };
+            errorbar.hide();
+            // cached rows of the tweet are updated, they are expanded with it later
+            for cached in &[&home, &mentions, &likes_view.timeline] {
+                let mut guard = match cached.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                timeline::actions::update(&mut guard, tweet.status_id(), &engagement);
             }
+            Some(engagement)
         });
     }
 
-    // event definition, when listboxrow is selected
+    // window setup
+    window.add(&paned);
+    window.show_all();
+    errorbar.hide();
+    undobar.hide();
+
+    // event definition, when re-authorize is requested from errorbar
     {
-        let home_timeline = home_timeline.clone();
+        let window = window.clone();
         let config = config.clone();
+        let token = token.clone();
 
-        listbox.connect_row_selected(move |_, listboxrow| {
-            let revealer = match listboxrow.clone() {
-                Some(listboxrow) => {
-                    match listboxrow.get_child() {
-                        Some(widget) => {
-                            match widget.downcast::<Revealer>() {
-                                Ok(revealer) => revealer,
-                                Err(err) => {
-                                    error!("{:?}", err);
-                                    return;
-                                },
-                            }
-                        },
-                        None => return,
-                    }
-                },
-                None => return,
+        errorbar.infobar.connect_response(move |infobar, response| if response == errorbar::RESPONSE_REAUTHORIZE {
+            preferences::reauthorize(&window, &config, &token);
+            if config.is_authorized() {
+                infobar.hide();
+            }
+        });
+    }
+
+    // verify credentials at startup
+    {
+        let client = match token.lock() {
+            Ok(guard) => api::Client::new(&config, &guard),
+            Err(poisoned) => api::Client::new(&config, &poisoned.into_inner()),
+        };
+        match api::verify_credentials(&client) {
+            Ok(user) => {
+                info!("credentials are verified");
+                let screen_name = user.find("screen_name").and_then(|v| v.as_string()).map(|s| s.to_owned());
+                timeline::utils::set_screen_name(screen_name);
+            },
+            Err(err) => {
+                error!("{:?}", err);
+                errorbar.show_error(&err);
+            },
+        }
+    }
+    {
+        let config = config.clone();
+        let listbox = listbox.clone();
+
+        let mut guard = match home_timeline.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut timeline = guard.deref_mut();
+        timeline::home::fixup_home(timeline, config.toml.home_timeline.limits.get());
+        match timeline::home::update_home(&listbox, timeline, false, false) {
+            Ok(_) => (),
+            Err(err) => {
+                error!("{:?}", err);
+                errorbar.show_render_error(&err);
+            },
+        }
+    }
+    {
+        let config = config.clone();
+        let mentions_listbox = mentions_listbox.clone();
+
+        let mut guard = match mentions_timeline.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let mut timeline = guard.deref_mut();
+        timeline::home::fixup_home(timeline, config.toml.mentions_timeline.limits.get());
+        match timeline::home::update_home(&mentions_listbox, timeline, false, false) {
+            Ok(_) => (),
+            Err(err) => {
+                error!("{:?}", err);
+                errorbar.show_render_error(&err);
+            },
+        }
+        mention_label.set_text(&timeline::utils::unread_label("Mention", timeline));
+    }
+
+    // event definition, when refresh_button is clicked
+    {
+        let listbox = listbox.clone();
+        let config = config.clone();
+        let home = home_timeline.clone();
+        let mentions_listbox = mentions_listbox.clone();
+        let mentions = mentions_timeline.clone();
+        let mention_label = mention_label.clone();
+        let dm_view = dm_view.clone();
+        let lists_view = lists_view.clone();
+        let search_view = search_view.clone();
+        let token = token.clone();
+        let snapshot = snapshot.clone();
+        let errorbar = errorbar.clone();
+
+        refresh_button.connect_clicked(move |_| {
+            let client = match token.lock() {
+                Ok(guard) => api::Client::new(&config, &guard),
+                Err(poisoned) => api::Client::new(&config, &poisoned.into_inner()),
             };
-            let box_revealer = match revealer.get_child() {
-                Some(widget) => {
-                    match widget.downcast::<Box>() {
-                        Ok(box_revealer) => box_revealer,
+            // an error of a timeline is reported and the other timelines are still refreshed
+            errorbar.hide();
+            let since_id = config.toml.home_timeline.last_update_id.get() as u64;
+            let count = timeline::utils::page_size(config.toml.home_timeline.limits.get());
+            match timeline::home::home_timeline(&client, Some(since_id), None, count) {
+                Ok(mut home_timeline) => {
+                    timeline::home::mark_gap(&mut home_timeline, since_id, count);
+                    match home_timeline.first() {
+                        Some(status) => config.toml.home_timeline.last_update_id.set(status.tweet.id),
+                        None => (),
+                    }
+                    // add tweets to home_timeline and update home_timeline,
+                    // tweets posted from compose are already in home
+                    let home_timeline = {
+                        let mut guard = match home.lock() {
+                            Ok(guard) => guard,
+                            Err(poisoned) => poisoned.into_inner(),
+                        };
+                        let limit = config.retention(&config.toml.home_timeline);
+                        timeline::home::merge_page(guard.deref_mut(), home_timeline, limit)
+                    };
+                    match timeline::home::update_home(&listbox, &home_timeline, true, false) {
+                        Ok(_) => (),
                         Err(err) => {
                             error!("{:?}", err);
-                            return;
+                            errorbar.show_render_error(&err);
+                        },
+                    }
+                },
+                Err(err) => {
+                    error!("{:?}", err);
+                    errorbar.show_error(&err);
+                },
+            }
+
+            match timeline::mentions::mentions_timeline(
+                &client,
+                Some(config.toml.mentions_timeline.last_update_id.get() as u64),
+                config.toml.mentions_timeline.limits.get() as i32,
+            ) {
+                Ok(mentions_timeline) => {
+                    match mentions_timeline.first() {
+                        Some(status) => config.toml.mentions_timeline.last_update_id.set(status.tweet.id),
+                        None => (),
+                    }
+                    // add tweets to mentions_timeline and update mentions_timeline
+                    {
+                        let mut guard = match mentions.lock() {
+                            Ok(guard) => guard,
+                            Err(poisoned) => poisoned.into_inner(),
+                        };
+                        let timeline = guard.deref_mut();
+                        let limits = config.toml.mentions_timeline.limits.get();
+                        timeline::utils::prepend(timeline, &mentions_timeline, limits);
+                        mention_label.set_text(&timeline::utils::unread_label("Mention", timeline));
+                    }
+                    match timeline::home::update_home(&mentions_listbox, &mentions_timeline, true, false) {
+                        Ok(_) => (),
+                        Err(err) => {
+                            error!("{:?}", err);
+                            errorbar.show_render_error(&err);
                         },
                     }
                 },
+                Err(err) => {
+                    error!("{:?}", err);
+                    errorbar.show_error(&err);
+                },
+            }
+
+            match dm_view.fetch(&client) {
+                Ok(_) => (),
+                Err(err) => {
+                    error!("{:?}", err);
+                    errorbar.show_error(&err);
+                },
+            }
+            match lists_view.fetch_all(&client) {
+                Ok(_) => (),
+                Err(err) => {
+                    error!("{:?}", err);
+                    errorbar.show_error(&err);
+                },
+            }
+            match search_view.fetch_all(&client) {
+                Ok(_) => (),
+                Err(err) => {
+                    error!("{:?}", err);
+                    errorbar.show_error(&err);
+                },
+            }
+            // the update thread requests tweets after the moved cursors
+            config.share(&token, &snapshot);
+        });
+    }
+
+    // event definition, when account is switched
+    {
+        let listbox = listbox.clone();
+        let config = config.clone();
+        let home = home_timeline.clone();
+        let mentions_listbox = mentions_listbox.clone();
+        let mentions = mentions_timeline.clone();
+        let mention_label = mention_label.clone();
+        let likes_view = likes_view.clone();
+        let dm_view = dm_view.clone();
+        let lists_view = lists_view.clone();
+        let search_view = search_view.clone();
+        let outbox_view = outbox_view.clone();
+        let token = token.clone();
+        let snapshot = snapshot.clone();
+        let cache_dir = cache_dir.clone();
+        let undobar = undobar.clone();
+
+        account_combobox.connect_changed(move |combobox| {
+            let name = match combobox.get_active_text() {
+                Some(name) => name,
                 None => return,
             };
-            let unread_image = match box_revealer.get_children()[2].clone().downcast::<Image>() {
-                Ok(unread_image) => unread_image,
+            let previous = config.account();
+            if name == previous {
+                return;
+            }
+            // deferred deletions are sent by the token of the previous account
+            undobar.flush();
+
+            match config.switch_account(&name) {
+                Ok(_) => (),
                 Err(err) => {
                     error!("{:?}", err);
                     return;
                 },
-            };
-            let id_label = match box_revealer.get_children()[4].clone().downcast::<Label>() {
-                Ok(id_label) => id_label,
+            }
+            match config.sync() {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+            {
+                let mut token = match token.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                *token = auth::token(&config);
+            }
+            config.share(&token, &snapshot);
+            // own tweets of the switched account can be deleted
+            let client = api::client(&config, &token);
+            match api::verify_credentials(&client) {
+                Ok(user) => {
+                    let screen_name = user.find("screen_name").and_then(|v| v.as_string()).map(|s| s.to_owned());
+                    timeline::utils::set_screen_name(screen_name);
+                },
+                Err(err) => {
+                    error!("{:?}", err);
+                    timeline::utils::set_screen_name(None);
+                },
+            }
+
+            // write cache of the previous account and load cache of the switched account
+            {
+                let mut guard = match home.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let timeline = guard.deref_mut();
+                // rows of history are not cached
+                timeline::home::fixup_home(timeline, config.toml.home_timeline.limits.get());
+                cache::switch(&cache_dir, vars::CACHE_HOME, &previous, &name, timeline);
+                timeline::home::fixup_home(timeline, config.toml.home_timeline.limits.get());
+                match timeline::home::update_home(&listbox, timeline, false, false) {
+                    Ok(_) => (),
+                    Err(err) => error!("{:?}", err),
+                }
+            }
+            {
+                let mut guard = match mentions.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                let timeline = guard.deref_mut();
+                cache::switch(&cache_dir, vars::CACHE_MENTIONS, &previous, &name, timeline);
+                timeline::home::fixup_home(timeline, config.toml.mentions_timeline.limits.get());
+                match timeline::home::update_home(&mentions_listbox, timeline, false, false) {
+                    Ok(_) => (),
+                    Err(err) => error!("{:?}", err),
+                }
+                mention_label.set_text(&timeline::utils::unread_label("Mention", timeline));
+            }
+            {
+                let mut guard = match likes_view.timeline.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                cache::switch(&cache_dir, vars::CACHE_LIKES, &previous, &name, guard.deref_mut());
+            }
+            likes_view.render();
+            {
+                let mut guard = match dm_view.conversations.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                cache::switch(&cache_dir, vars::CACHE_DIRECT_MESSAGES, &previous, &name, guard.deref_mut());
+            }
+            dm_view.render();
+            lists_view.switch(&previous);
+            search_view.switch(&previous);
+            outbox_view.switch(&previous);
+        });
+    }
+
+    // event definition, when account_add_button is clicked
+    {
+        let window = window.clone();
+        let config = config.clone();
+        let account_combobox = account_combobox.clone();
+
+        account_add_button.connect_clicked(move |_| {
+            let (token, screen_name) = match auth::authorize_dialog(Some(&window), &config) {
+                Ok(access) => access,
+                Err(auth::AuthError::Cancel) => return,
                 Err(err) => {
                     error!("{:?}", err);
                     return;
                 },
             };
+            let result = match token {
+                egg_mode::Token::Access { ref access, .. } => {
+                    config.add_account(screen_name.clone(), access.key.to_string(), access.secret.to_string())
+                },
+                egg_mode::Token::Bearer(_) => return,
+            };
+            match result {
+                Ok(_) => (),
+                Err(err) => {
+                    error!("{:?}", err);
+                    return;
+                },
+            }
+            // activate the added account through account_combobox changed event
+            account_combobox.append_text(&screen_name);
+            account_combobox.set_active(config.account_names().len() as i32 - 1);
+        });
+    }
 
-            // clear unread image to empty
-            unread_image.clear();
-            unread_image.set_padding(vars::UNREAD_IMAGE_SIZE, vars::UNREAD_IMAGE_SIZE);
+    // event definition, when listboxrow is selected
+    {
+        let home_timeline = home_timeline.clone();
+        let config = config.clone();
 
+        listbox.connect_row_selected(move |_, listboxrow| {
+            let mut guard = match home_timeline.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
             // update config.toml.home_timeline.last_read_id
-            let id: u64 = match id_label.get_text() {
-                Some(id_str) => {
-                    match u64::from_str_radix(&id_str, 10) {
-                        Ok(id) => id,
-                        Err(err) => {
-                            error!("{}", err);
-                            return;
-                        },
-                    }
+            if let Some(id) = timeline::home::select_row(listboxrow, guard.deref_mut()) {
+                config.toml.home_timeline.last_read_id.set(id);
+            }
+        });
+    }
+
+    // event definition, when the bottom of home is reached, older tweets are appended up to history
+    {
+        let config = config.clone();
+        let token = token.clone();
+        let errorbar = errorbar.clone();
+        let listbox = listbox.clone();
+        let home = home_timeline.clone();
+
+        scrolled_window.connect_edge_reached(move |_, position| {
+            if position != gtk::PositionType::Bottom {
+                return;
+            }
+            let max_id = {
+                let guard = match home.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                if guard.len() >= config.retention(&config.toml.home_timeline) {
+                    return;
+                }
+                match guard.last() {
+                    Some(row) => row.tweet.id - 1,
+                    None => return,
+                }
+            };
+            let client = api::client(&config, &token);
+            let count = timeline::utils::page_size(config.toml.home_timeline.limits.get());
+            let mut older = match timeline::home::home_timeline(&client, None, Some(max_id), count) {
+                Ok(older) => older,
+                Err(err) => {
+                    error!("{:?}", err);
+                    errorbar.show_error(&err);
+                    return;
                 },
-                None => return,
             };
-            // update home_timeline unread flag to clear
+            errorbar.hide();
+            // history keeps the unread flag only newer than last_read_id, older rows are counted as read
+            // although they may have never been shown, otherwise the whole history adds to the unread count
+            let last_read_id = config.toml.home_timeline.last_read_id.get();
+            for row in older.iter_mut() {
+                row.unread = row.tweet.id > last_read_id;
+            }
             {
-                let listboxrow = match listboxrow.clone() {
-                    Some(listboxrow) => listboxrow,
-                    None => return,
-                };
-
-                let mut guard = match home_timeline.lock() {
+                let mut guard = match home.lock() {
                     Ok(guard) => guard,
                     Err(poisoned) => poisoned.into_inner(),
                 };
-                let timeline = guard.deref_mut();
+                guard.extend(older.clone());
+            }
+            match timeline::home::append_home(&listbox, &older) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+        });
+    }
 
-                for row in timeline {
-                    if row.tweet.id == id {
-                        row.unread = false;
-                        // expand selected listboxrow
-                        listboxrow.remove(&revealer);
-                        let revealer = match timeline::home::create_expanded_revealer(row.clone()) {
-                            Ok(revealer) => revealer,
-                            Err(err) => {
-                                error!("{:?}", err);
-                                return;
-                            },
-                        };
-                        listboxrow.add(&revealer);
-                        match timeline::home::show_listboxrow(&listboxrow) {
-                            Ok(_) => (),
-                            Err(err) => {
-                                error!("{:?}", err);
-                                return;
-                            },
-                        }
-                        break;
-                    }
-                }
-                config.toml.home_timeline.last_read_id.set(id);
+    // event definition, when mentions listboxrow is selected
+    {
+        let mentions_timeline = mentions_timeline.clone();
+        let mention_label = mention_label.clone();
+        let config = config.clone();
+
+        mentions_listbox.connect_row_selected(move |_, listboxrow| {
+            let mut guard = match mentions_timeline.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            let timeline = guard.deref_mut();
+            // update config.toml.mentions_timeline.last_read_id
+            if let Some(id) = timeline::home::select_row(listboxrow, timeline) {
+                config.toml.mentions_timeline.last_read_id.set(id);
             }
+            mention_label.set_text(&timeline::utils::unread_label("Mention", timeline));
         });
     }
 
     {
         // create threads send signal, update timeline
-        let config = config.clone();
-        let token = token.clone();
+        let snapshot = snapshot.clone();
+        let outbox = outbox_view.outbox.clone();
+        let cache_media_dir = cache_media_dir.clone();
 
         thread::spawn(move || {
             let retry_secs = 60;
             let duration = 600;
+            // current returns the snapshot of the active account shared by the gtk thread
+            let current = || match snapshot.lock() {
+                Ok(guard) => guard.clone(),
+                Err(poisoned) => poisoned.into_inner().clone(),
+            };
+            // send passes the result to the gtk thread, it fails only after the gtk thread quits,
+            // and the update thread ends then
+            let send = |account: String, kind: timeline::Kind, result| match tx.send((account, kind, result)) {
+                Ok(_) => true,
+                Err(err) => {
+                    error!("{:?}", err);
+                    false
+                },
+            };
+            // drain_outbox posts queued tweets which are due, it is tried every retry_secs
+            let drain_outbox = |session: &config::Snapshot| {
+                match outbox::drain(&session.client, &outbox, &session.account, &cache_media_dir) {
+                    Some(posted) => send(session.account.clone(), timeline::Kind::Outbox, Ok(posted)),
+                    None => true,
+                }
+            };
             loop {
-                let timeline = match timeline::home::home_timeline(
-                    token.as_ref(),
-                    Some(config.toml.home_timeline.last_update_id.get() as u64),
-                    config.toml.home_timeline.limits.get() as i32,
-                ) {
-                    Ok(timeline) => timeline,
+                let session = current();
+                let account = session.account.clone();
+                let client = session.client.clone();
+                if !drain_outbox(&session) {
+                    return;
+                }
+                let since_id = session.home_timeline.last_update_id.get() as u64;
+                let count = timeline::utils::page_size(session.home_timeline.limits.get());
+                let timeline = match timeline::home::home_timeline(&client, Some(since_id), None, count) {
+                    Ok(mut timeline) => {
+                        timeline::home::mark_gap(&mut timeline, since_id, count);
+                        timeline
+                    },
                     Err(err) => {
                         error!("{:?}", err);
-                        debug!("it will try it after {} seconds", retry_secs);
-                        thread::sleep(time::Duration::from_secs(retry_secs));
+                        // revoked token is not recovered until re-authorization, so wait longer
+                        let wait_secs = match errorbar::is_unauthorized(&err) {
+                            true => duration,
+                            false => retry_secs,
+                        };
+                        if !send(account, timeline::Kind::Home, Err(err)) {
+                            return;
+                        }
+                        debug!("it will try it after {} seconds", wait_secs);
+                        thread::sleep(time::Duration::from_secs(wait_secs));
                         continue;
                     },
                 };
-                match timeline.first() {
-                    Some(status) => {
-                        config.toml.home_timeline.last_update_id.set(
-                            status.tweet.id,
-                        )
-                    },
-                    None => (),
+                // cursors are moved by the gtk thread when it receives tweets
+                if !send(account.clone(), timeline::Kind::Home, Ok(timeline)) {
+                    return;
                 }
-                match tx.send(timeline.clone()) {
-                    Ok(_) => (),
-                    Err(err) => {
-                        error!("{:?}", err);
-                        panic!("{:?}", err);
+
+                let mentions = timeline::mentions::mentions_timeline(
+                    &client,
+                    Some(session.mentions_timeline.last_update_id.get() as u64),
+                    session.mentions_timeline.limits.get() as i32,
+                );
+                if !send(account.clone(), timeline::Kind::Mentions, mentions) {
+                    return;
+                }
+
+                // direct messages are sent by conversation
+                let results = match timeline::dm::direct_messages(
+                    &client,
+                    Some(session.direct_messages.last_update_id.get()),
+                    session.direct_messages.limits.get() as i32,
+                ) {
+                    Ok(conversations) => {
+                        conversations
+                            .into_iter()
+                            .map(|conversation| {
+                                (timeline::Kind::DirectMessages(conversation.screen_name), Ok(conversation.messages))
+                            })
+                            .collect()
                     },
+                    Err(err) => vec![(timeline::Kind::DirectMessages(String::new()), Err(err))],
                 };
-                thread::sleep(time::Duration::from_secs(duration));
+                for (kind, result) in results {
+                    if !send(account.clone(), kind, result) {
+                        return;
+                    }
+                }
+
+                // scheduled tweets are posted while waiting for the next update,
+                // the snapshot is read again each time because the account may be switched meanwhile
+                let mut waited = 0;
+                while waited < duration {
+                    thread::sleep(time::Duration::from_secs(retry_secs));
+                    waited += retry_secs;
+                    if !drain_outbox(&current()) {
+                        return;
+                    }
+                }
             }
         });
     }
@@ -547,29 +1486,99 @@ pub fn main() {
         // receive signal from threads, update gtk widgets thread
         let config = config.clone();
         let home = home_timeline.clone();
+        let mentions = mentions_timeline.clone();
+        let errorbar = errorbar.clone();
 
         let listbox = listbox.clone();
+        let mentions_listbox = mentions_listbox.clone();
+        let mention_label = mention_label.clone();
+        let dm_view = dm_view.clone();
+        let outbox_view = outbox_view.clone();
+        let token = token.clone();
+        let snapshot = snapshot.clone();
 
         let refresh_timeline = move || {
+            // the update thread reads changes of config at most a poll later
+            config.share(&token, &snapshot);
             debug!("pooling try to receive from channel");
-            let tweets = match rx.try_recv() {
-                Ok(tweets) => tweets,
+            let (kind, result) = match rx.try_recv() {
+                Ok((account, kind, result)) => {
+                    // the outbox of the account is written even when tweets of it are posted after switching
+                    if kind == timeline::Kind::Outbox {
+                        outbox_view.write(&account);
+                    }
+                    // drop tweets fetched before the account is switched
+                    if account != config.account() {
+                        return glib::Continue(true);
+                    }
+                    (kind, result)
+                },
                 Err(_) => return glib::Continue(true),
             };
-            {
-                let mut guard = match home.lock() {
-                    Ok(guard) => guard,
-                    Err(poisoned) => poisoned.into_inner(),
-                };
-                let mut index = 0;
-                for row in tweets.clone() {
-                    guard.insert(index, row.clone());
-                    index += 1;
+            // tweets posted from the outbox do not change the state of the errorbar
+            if kind == timeline::Kind::Outbox {
+                for row in result.unwrap_or(Vec::new()).into_iter().rev() {
+                    timeline::home::insert_posted(&listbox, &home, row, config.retention(&config.toml.home_timeline));
                 }
-                let mut timeline = guard.deref_mut();
-                timeline::home::fixup_home(timeline, config.toml.home_timeline.limits.get());
+                outbox_view.render();
+                return glib::Continue(true);
+            }
+            let tweets = match result {
+                Ok(tweets) => tweets,
+                Err(err) => {
+                    errorbar.show_error(&err);
+                    return glib::Continue(true);
+                },
+            };
+            errorbar.hide();
+            match kind {
+                timeline::Kind::Home => {
+                    if let Some(row) = tweets.first() {
+                        let cursor = &config.toml.home_timeline.last_update_id;
+                        cursor.set(cmp::max(cursor.get(), row.tweet.id));
+                    }
+                    // tweets posted from compose are already in home
+                    let tweets = {
+                        let mut guard = match home.lock() {
+                            Ok(guard) => guard,
+                            Err(poisoned) => poisoned.into_inner(),
+                        };
+                        let limit = config.retention(&config.toml.home_timeline);
+                        timeline::home::merge_page(guard.deref_mut(), tweets, limit)
+                    };
+                    if let Err(err) = timeline::home::update_home(&listbox, &tweets, true, false) {
+                        error!("{:?}", err);
+                        errorbar.show_render_error(&err);
+                    }
+                },
+                timeline::Kind::Mentions => {
+                    if let Some(row) = tweets.first() {
+                        let cursor = &config.toml.mentions_timeline.last_update_id;
+                        cursor.set(cmp::max(cursor.get(), row.tweet.id));
+                    }
+                    {
+                        let mut guard = match mentions.lock() {
+                            Ok(guard) => guard,
+                            Err(poisoned) => poisoned.into_inner(),
+                        };
+                        let timeline = guard.deref_mut();
+                        timeline::utils::prepend(timeline, &tweets, config.toml.mentions_timeline.limits.get());
+                        mention_label.set_text(&timeline::utils::unread_label("Mention", timeline));
+                    }
+                    if let Err(err) = timeline::home::update_home(&mentions_listbox, &tweets, true, false) {
+                        error!("{:?}", err);
+                        errorbar.show_render_error(&err);
+                    }
+                },
+                timeline::Kind::DirectMessages(screen_name) => {
+                    if let Some(row) = tweets.first() {
+                        let cursor = &config.toml.direct_messages.last_update_id;
+                        cursor.set(cmp::max(cursor.get(), row.tweet.id));
+                    }
+                    dm_view.merge(&screen_name, &tweets)
+                },
+                timeline::Kind::Outbox => (),
             }
-            let _ = timeline::home::update_home(&listbox, &tweets, true, false);
 
             return glib::Continue(true);
         };
@@ -582,6 +1591,8 @@ pub fn main() {
         let config = config.clone();
 
         window.connect_delete_event(move |_, _| {
+            // deferred deletions are sent before caches are written
+            undobar.flush();
             gtk::main_quit();
 
             // synchronize config to config file
@@ -595,12 +1606,56 @@ pub fn main() {
 
             // write cache to each cache file
             {
-                let cache_home = cache_dir.clone().join(vars::CACHE_HOME);
-                let guard = match home_timeline.lock() {
+                let cache_home = cache::path(&cache_dir, vars::CACHE_HOME, &config.account());
+                let mut guard = match home_timeline.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                // rows of history are not cached
+                timeline::home::fixup_home(guard.deref_mut(), config.toml.home_timeline.limits.get());
+                match cache::write(cache_home, guard.deref()) {
+                    Ok(_) => (),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        panic!("{:?}", err)
+                    },
+                }
+            }
+            {
+                let cache_mentions = cache::path(&cache_dir, vars::CACHE_MENTIONS, &config.account());
+                let guard = match mentions_timeline.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                match cache::write(cache_mentions, guard.deref()) {
+                    Ok(_) => (),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        panic!("{:?}", err)
+                    },
+                }
+            }
+            {
+                let cache_likes = cache::path(&cache_dir, vars::CACHE_LIKES, &config.account());
+                let guard = match likes_view.timeline.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                match cache::write(cache_likes, guard.deref()) {
+                    Ok(_) => (),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        panic!("{:?}", err)
+                    },
+                }
+            }
+            {
+                let cache_direct_messages = cache::path(&cache_dir, vars::CACHE_DIRECT_MESSAGES, &config.account());
+                let guard = match dm_view.conversations.lock() {
                     Ok(guard) => guard,
                     Err(poisoned) => poisoned.into_inner(),
                 };
-                match cache::write(cache_home, &guard) {
+                match cache::write(cache_direct_messages, guard.deref()) {
                     Ok(_) => (),
                     Err(err) => {
                         error!("{:?}", err);
@@ -608,6 +1663,9 @@ pub fn main() {
                     },
                 }
             }
+            lists_view.write(&config.account());
+            search_view.write(&config.account());
+            outbox_view.write(&config.account());
 
             Inhibit(false)
         });
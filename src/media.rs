@@ -0,0 +1,423 @@
+extern crate gdk_pixbuf;
+extern crate rustc_serialize;
+
+use gdk_pixbuf::Pixbuf;
+use gtk;
+use gtk::prelude::*;
+use rustc_serialize::base64::{STANDARD, ToBase64};
+use rustc_serialize::json;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path;
+use std::rc::Rc;
+use std::thread;
+use std::time;
+
+// MAX_IMAGES is the number of images attached to a tweet, a GIF or a video is attached alone
+pub const MAX_IMAGES: usize = 4;
+
+// MAX_IMAGE_BYTES is the maximum size of an image, a GIF and a video are checked by twitter
+const MAX_IMAGE_BYTES: u64 = 5 * 1024 * 1024;
+
+// CHUNK_BYTES is the size of a chunk sent by APPEND command
+const CHUNK_BYTES: usize = 1024 * 1024;
+
+// UPLOAD_PROGRESS is the part of the progress taken by sending a GIF or a video, processing it takes the rest
+const UPLOAD_PROGRESS: f64 = 0.5;
+
+// THUMBNAIL_SIZE is the width and height of thumbnails in the compose dialog
+const THUMBNAIL_SIZE: i32 = 96;
+
+// MediaError
+#[derive(Debug)]
+pub enum MediaError {
+    Io(io::Error),
+    Api(::api::ApiError),
+    Unsupported(String),
+    Processing(String),
+}
+
+impl From<io::Error> for MediaError {
+    fn from(err: io::Error) -> MediaError {
+        MediaError::Io(err)
+    }
+}
+
+impl From<::api::ApiError> for MediaError {
+    fn from(err: ::api::ApiError) -> MediaError {
+        MediaError::Api(err)
+    }
+}
+
+impl From<String> for MediaError {
+    fn from(err: String) -> MediaError {
+        MediaError::Api(::api::ApiError::InvalidResponse(err))
+    }
+}
+
+// describe returns the message of err shown in the compose dialog
+pub fn describe(err: &MediaError) -> String {
+    match *err {
+        MediaError::Io(ref err) => format!("{}", err),
+        MediaError::Api(ref err) => ::errorbar::describe(err),
+        MediaError::Unsupported(ref err) => err.clone(),
+        MediaError::Processing(ref err) => format!("media processing failed: {}", err),
+    }
+}
+
+// Media is a file attached to a tweet
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct Media {
+    pub filename: String,
+    pub media_type: String,
+    pub alt_text: String,
+}
+
+impl Media {
+    // open returns the media of filename, its type is decided by the extension
+    pub fn open(filename: &str) -> Result<Media, MediaError> {
+        let extension = path::Path::new(filename)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_lowercase())
+            .unwrap_or(String::new());
+        let media_type = match extension.as_str() {
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "webp" => "image/webp",
+            "gif" => "image/gif",
+            "mp4" => "video/mp4",
+            _ => return Err(MediaError::Unsupported(format!("{} is not an image, a GIF or a video", filename))),
+        };
+        let media = Media {
+            filename: filename.to_owned(),
+            media_type: media_type.to_owned(),
+            alt_text: String::new(),
+        };
+        if media.is_image() && try!(try!(File::open(filename)).metadata()).len() > MAX_IMAGE_BYTES {
+            return Err(MediaError::Unsupported(format!("{} is larger than 5MB", filename)));
+        }
+        Ok(media)
+    }
+
+    // is_image returns true when media is a still image, GIF is not
+    pub fn is_image(&self) -> bool {
+        self.media_type.starts_with("image/") && self.media_type != "image/gif"
+    }
+
+    fn category(&self) -> &'static str {
+        match self.media_type.as_str() {
+            "image/gif" => "tweet_gif",
+            "video/mp4" => "tweet_video",
+            _ => "tweet_image",
+        }
+    }
+}
+
+// can_attach returns true when media is attached with attached ones,
+// a tweet has up to four images or one GIF or video
+pub fn can_attach(attached: &Vec<Media>, media: &Media) -> bool {
+    match media.is_image() {
+        true => attached.len() < MAX_IMAGES && attached.iter().all(|attached| attached.is_image()),
+        false => attached.is_empty(),
+    }
+}
+
+// can_attach_all returns true when all of media are attached to a tweet together
+pub fn can_attach_all(media: &Vec<Media>) -> bool {
+    let mut attached = Vec::new();
+    for media in media {
+        if !can_attach(&attached, media) {
+            return false;
+        }
+        attached.push(media.clone());
+    }
+    true
+}
+
+// upload uploads media by chunked upload and returns its media id,
+// progress is called with the ratio of sent bytes and processed ones which do not overlap
+pub fn upload<F: Fn(f64)>(client: &::api::Client, media: &Media, progress: F) -> Result<String, MediaError> {
+    let uploaded = match media.is_image() {
+        true => 1.0,
+        false => UPLOAD_PROGRESS,
+    };
+    let mut bytes = Vec::new();
+    try!(try!(File::open(&media.filename)).read_to_end(&mut bytes));
+    let url = client.upload_url("media/upload.json");
+
+    let mut params = BTreeMap::new();
+    params.insert("command".to_owned(), "INIT".to_owned());
+    params.insert("total_bytes".to_owned(), format!("{}", bytes.len()));
+    params.insert("media_type".to_owned(), media.media_type.clone());
+    params.insert("media_category".to_owned(), media.category().to_owned());
+    let response = try!(client.post_url(&url, &params));
+    let media_id = try!(::timeline::home::string_field(&response, "media_id_string"));
+
+    let chunks = bytes.chunks(CHUNK_BYTES).count();
+    for (index, chunk) in bytes.chunks(CHUNK_BYTES).enumerate() {
+        let mut params = BTreeMap::new();
+        params.insert("command".to_owned(), "APPEND".to_owned());
+        params.insert("media_id".to_owned(), media_id.clone());
+        params.insert("segment_index".to_owned(), format!("{}", index));
+        params.insert("media_data".to_owned(), chunk.to_base64(STANDARD));
+        try!(client.post_url(&url, &params));
+        progress(uploaded * (index + 1) as f64 / chunks as f64);
+    }
+
+    let mut params = BTreeMap::new();
+    params.insert("command".to_owned(), "FINALIZE".to_owned());
+    params.insert("media_id".to_owned(), media_id.clone());
+    let mut response = try!(client.post_url(&url, &params));
+
+    // GIF and video are processed asynchronously after FINALIZE
+    while let Some(processing_info) = response.find("processing_info").cloned() {
+        let state = try!(::timeline::home::string_field(&processing_info, "state"));
+        match state.as_str() {
+            "succeeded" => break,
+            "failed" => {
+                let message = processing_info
+                    .find_path(&["error", "message"])
+                    .and_then(|v| v.as_string())
+                    .unwrap_or("unknown error");
+                return Err(MediaError::Processing(message.to_owned()));
+            },
+            _ => (),
+        }
+        if let Some(percent) = processing_info.find("progress_percent").and_then(|v| v.as_u64()) {
+            progress(uploaded + (1.0 - uploaded) * percent as f64 / 100.0);
+        }
+        let check_after_secs = processing_info.find("check_after_secs").and_then(|v| v.as_u64()).unwrap_or(1);
+        thread::sleep(time::Duration::from_secs(check_after_secs));
+
+        let mut params = BTreeMap::new();
+        params.insert("command".to_owned(), "STATUS".to_owned());
+        params.insert("media_id".to_owned(), media_id.clone());
+        response = try!(client.get_url(&url, &params));
+    }
+
+    if !media.alt_text.is_empty() {
+        let mut alt_text = BTreeMap::new();
+        alt_text.insert("text".to_owned(), json::Json::String(media.alt_text.clone()));
+        let mut body = BTreeMap::new();
+        body.insert("media_id".to_owned(), json::Json::String(media_id.clone()));
+        body.insert("alt_text".to_owned(), json::Json::Object(alt_text));
+        try!(client.post_json(&client.upload_url("media/metadata/create.json"), &json::Json::Object(body)));
+    }
+    Ok(media_id)
+}
+
+// uri_filename returns the local filename of a dropped file uri
+pub fn uri_filename(uri: &str) -> Option<String> {
+    if !uri.starts_with("file://") {
+        return None;
+    }
+    let mut bytes = Vec::new();
+    let mut rest = uri["file://".len()..].trim_right().bytes();
+    while let Some(byte) = rest.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+        let hex: Vec<u8> = rest.by_ref().take(2).collect();
+        match String::from_utf8(hex).ok().and_then(|hex| u8::from_str_radix(&hex, 16).ok()) {
+            Some(byte) => bytes.push(byte),
+            None => return None,
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+// remove_pasted removes files of pasted images in media_dir after the tweet is posted or discarded
+pub fn remove_pasted(media: &Vec<Media>, media_dir: &path::Path) {
+    for media in media {
+        if path::Path::new(&media.filename).starts_with(media_dir) {
+            match fs::remove_file(&media.filename) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+        }
+    }
+}
+
+// MediaBox shows attached media with thumbnails and alt text entries in the compose dialog
+#[derive(Clone)]
+pub struct MediaBox {
+    pub container: gtk::Box,
+    pub progress_bar: gtk::ProgressBar,
+    box_media: gtk::Box,
+    media: Rc<RefCell<Vec<Media>>>,
+    // reopened is media of a reopened draft, their files are kept when they are removed from the draft
+    // until the draft is sent
+    reopened: Rc<RefCell<Vec<Media>>>,
+    // media_dir keeps pasted images as long as a queued tweet refers to them
+    media_dir: path::PathBuf,
+}
+
+impl MediaBox {
+    pub fn new(media_dir: path::PathBuf) -> MediaBox {
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 4);
+        let box_media = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+        let progress_bar = gtk::ProgressBar::new();
+        progress_bar.set_no_show_all(true);
+        container.pack_start(&box_media, false, false, 0);
+        container.pack_start(&progress_bar, false, false, 0);
+
+        MediaBox {
+            container: container,
+            progress_bar: progress_bar,
+            box_media: box_media,
+            media: Rc::new(RefCell::new(Vec::new())),
+            reopened: Rc::new(RefCell::new(Vec::new())),
+            media_dir: media_dir,
+        }
+    }
+
+    // media returns attached media with their alt text
+    pub fn media(&self) -> Vec<Media> {
+        self.media.borrow().clone()
+    }
+
+    // set_media replaces attached media by the ones of a reopened draft
+    pub fn set_media(&self, media: Vec<Media>) {
+        *self.reopened.borrow_mut() = media.clone();
+        *self.media.borrow_mut() = media;
+        self.render();
+    }
+
+    // attach attaches the file of filename and shows its thumbnail
+    pub fn attach(&self, filename: &str) -> Result<(), MediaError> {
+        let media = try!(Media::open(filename));
+        if !can_attach(&self.media.borrow(), &media) {
+            return Err(MediaError::Unsupported(
+                format!("up to {} images or one GIF or video can be attached", MAX_IMAGES),
+            ));
+        }
+        self.media.borrow_mut().push(media);
+        self.render();
+        Ok(())
+    }
+
+    // attach_pixbuf attaches the pasted image, it is saved as a png file in media_dir
+    pub fn attach_pixbuf(&self, pixbuf: &Pixbuf) -> Result<(), MediaError> {
+        let millis = match time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_secs() * 1000 + elapsed.subsec_nanos() as u64 / 1000000,
+            Err(_) => 0,
+        };
+        let filename = self.media_dir.join(format!("pasted-{}.png", millis));
+        let filename = match filename.to_str() {
+            Some(filename) => filename.to_owned(),
+            None => return Err(MediaError::Unsupported("cache directory is not valid unicode".to_owned())),
+        };
+        match pixbuf.savev(&filename, "png", &[]) {
+            Ok(_) => self.attach(&filename),
+            Err(err) => Err(MediaError::Unsupported(format!("{:?}", err))),
+        }
+    }
+
+    // render rebuilds thumbnails of attached media
+    fn render(&self) {
+        for widget in self.box_media.get_children() {
+            self.box_media.remove(&widget);
+        }
+        for (index, media) in self.media.borrow().iter().enumerate() {
+            let image = match Pixbuf::new_from_file_at_scale(&media.filename, THUMBNAIL_SIZE, THUMBNAIL_SIZE, true) {
+                Ok(pixbuf) => gtk::Image::new_from_pixbuf(Some(&pixbuf)),
+                // a video has no thumbnail
+                Err(_) => gtk::Image::new_from_icon_name("video-x-generic", 6),
+            };
+
+            let entry_alt_text = gtk::Entry::new();
+            entry_alt_text.set_placeholder_text(Some("alt text"));
+            entry_alt_text.set_text(&media.alt_text);
+            entry_alt_text.set_width_chars(12);
+            {
+                let media = self.media.clone();
+
+                entry_alt_text.connect_changed(move |entry| {
+                    if let Some(media) = media.borrow_mut().get_mut(index) {
+                        media.alt_text = entry.get_text().unwrap_or(String::new());
+                    }
+                });
+            }
+
+            let button_remove = gtk::Button::new_from_icon_name("window-close", 1);
+            button_remove.set_relief(gtk::ReliefStyle::None);
+            {
+                let view = self.clone();
+
+                button_remove.connect_clicked(move |_| {
+                    // the file of a pasted image is not referred to once it is removed, unless the reopened draft does
+                    let removed = view.media.borrow_mut().remove(index);
+                    if view.reopened.borrow().iter().all(|media| media.filename != removed.filename) {
+                        remove_pasted(&vec![removed], &view.media_dir);
+                    }
+                    view.render();
+                });
+            }
+
+            let box_header = gtk::Box::new(gtk::Orientation::Horizontal, 2);
+            box_header.pack_start(&entry_alt_text, true, true, 0);
+            box_header.pack_start(&button_remove, false, false, 0);
+
+            let box_card = gtk::Box::new(gtk::Orientation::Vertical, 2);
+            box_card.pack_start(&image, false, false, 0);
+            box_card.pack_start(&box_header, false, false, 0);
+            self.box_media.pack_start(&box_card, false, false, 0);
+        }
+        self.box_media.show_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn media(media_type: &str) -> Media {
+        Media {
+            filename: String::new(),
+            media_type: media_type.to_owned(),
+            alt_text: String::new(),
+        }
+    }
+
+    #[test]
+    fn can_attach_up_to_four_images() {
+        let mut attached = Vec::new();
+        for _ in 0..MAX_IMAGES {
+            assert!(can_attach(&attached, &media("image/png")));
+            attached.push(media("image/png"));
+        }
+        assert!(!can_attach(&attached, &media("image/jpeg")));
+    }
+
+    #[test]
+    fn can_attach_gif_or_video_alone() {
+        assert!(can_attach(&Vec::new(), &media("image/gif")));
+        assert!(can_attach(&Vec::new(), &media("video/mp4")));
+        assert!(!can_attach(&vec![media("image/png")], &media("image/gif")));
+        assert!(!can_attach(&vec![media("video/mp4")], &media("video/mp4")));
+        assert!(!can_attach(&vec![media("image/gif")], &media("image/png")));
+    }
+
+    #[test]
+    fn can_attach_all_checks_media_together() {
+        assert!(can_attach_all(&Vec::new()));
+        assert!(can_attach_all(&vec![media("image/png"), media("image/webp")]));
+        assert!(!can_attach_all(&vec![media("image/png"), media("video/mp4")]));
+        assert!(!can_attach_all(&vec![media("image/png"); MAX_IMAGES + 1]));
+    }
+
+    #[test]
+    fn media_open_decides_type_by_extension() {
+        assert!(Media::open("clip.MP4").map(|media| media.media_type == "video/mp4").unwrap_or(false));
+        match Media::open("notes.txt") {
+            Err(MediaError::Unsupported(_)) => (),
+            other => panic!("{:?}", other),
+        }
+    }
+}
@@ -0,0 +1,55 @@
+// The rule of the mute list is shared by rustytwit and stream-notification, both of them read muted of config.toml
+
+// ID_PREFIX marks an entry of the mute list as a user id, the other entries are screen names
+pub const ID_PREFIX: &'static str = "id:";
+
+// normalize trims an entry of the mute list, and strips @ of a screen name
+pub fn normalize(entry: &str) -> String {
+    let entry = entry.trim();
+    match entry.starts_with(ID_PREFIX) {
+        true => format!("{}{}", ID_PREFIX, entry[ID_PREFIX.len()..].trim()),
+        false => entry.trim_left_matches('@').to_owned(),
+    }
+}
+
+// is_muted_user returns true when entry of the mute list is the id or the screen name of the user,
+// an entry without ID_PREFIX is a screen name even when it is all digits
+pub fn is_muted_user(entry: &str, id: Option<u64>, screen_name: &str) -> bool {
+    let entry = normalize(entry);
+    match entry.starts_with(ID_PREFIX) {
+        true => entry[ID_PREFIX.len()..].parse::<u64>().ok().map(|muted| id == Some(muted)).unwrap_or(false),
+        false => !entry.is_empty() && entry.to_lowercase() == screen_name.to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_strips_at_and_spaces() {
+        assert_eq!(normalize(" @Someone "), "Someone");
+        assert_eq!(normalize("id: 12345"), "id:12345");
+    }
+
+    #[test]
+    fn is_muted_user_by_id() {
+        assert!(is_muted_user("id:12345", Some(12345), "someone"));
+        assert!(!is_muted_user("id:12345", Some(54321), "someone"));
+        assert!(!is_muted_user("id:12345", None, "someone"));
+        assert!(!is_muted_user("id:someone", Some(12345), "someone"));
+    }
+
+    #[test]
+    fn is_muted_user_by_screen_name() {
+        assert!(is_muted_user("@SomeOne", Some(12345), "someone"));
+        assert!(!is_muted_user("other", Some(12345), "someone"));
+        assert!(!is_muted_user("", Some(12345), ""));
+    }
+
+    #[test]
+    fn is_muted_user_digits_are_screen_name() {
+        assert!(is_muted_user("12345", Some(1), "12345"));
+        assert!(!is_muted_user("12345", Some(12345), "someone"));
+    }
+}
@@ -0,0 +1,487 @@
+extern crate chrono;
+extern crate egg_mode;
+
+use chrono::{Local, TimeZone};
+use gtk;
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::path;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use timeline::home::TimelineRow;
+
+// Entry is a queued tweet or thread, it is posted at scheduled_at or when the network is available again.
+// a thread which failed halfway keeps the posted count and is resumed from the failed tweet
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct Entry {
+    pub id: i64,
+    pub thread: ::compose::Thread,
+    // scheduled_at is unix time of the local time to post, None posts it as soon as possible
+    pub scheduled_at: Option<i64>,
+    pub error: Option<String>,
+    // held is set when posting failed by other than the network, it is posted by retry only
+    pub held: bool,
+    // in_flight is set while the entry is posted or edited, it is neither posted again nor edited meanwhile
+    pub in_flight: bool,
+}
+
+// Outbox is entries queued by each account, an entry is posted only by the token of its account
+pub type Outbox = BTreeMap<String, Vec<Entry>>;
+
+impl Entry {
+    pub fn new(thread: ::compose::Thread, scheduled_at: Option<i64>) -> Entry {
+        let now = Local::now();
+        Entry {
+            id: now.timestamp() * 1000 + now.timestamp_subsec_millis() as i64,
+            thread: thread,
+            scheduled_at: scheduled_at,
+            error: None,
+            held: false,
+            in_flight: false,
+        }
+    }
+
+    // is_due returns true when the entry is posted by drain at now
+    pub fn is_due(&self, now: i64) -> bool {
+        !self.held && !self.in_flight && self.scheduled_at.map(|scheduled_at| scheduled_at <= now).unwrap_or(true)
+    }
+
+    // status returns the queue status shown in the outbox
+    pub fn status(&self) -> String {
+        let status = match (self.in_flight, self.held, self.error.as_ref(), self.scheduled_at) {
+            (true, _, _, _) => "posting".to_owned(),
+            (_, true, Some(error), _) => format!("failed: {}", error),
+            (_, false, Some(error), _) => format!("waiting for the network: {}", error),
+            (_, _, None, Some(scheduled_at)) => {
+                format!("scheduled at {}", Local.timestamp(scheduled_at, 0).format(::compose::SCHEDULE_FORMAT))
+            },
+            _ => "waiting".to_owned(),
+        };
+        match self.thread.posted {
+            0 => status,
+            posted => format!("posted {} of {} tweets, {}", posted, self.thread.drafts.len(), status),
+        }
+    }
+}
+
+// is_offline returns true when err is caused by the network, the tweet is queued then
+pub fn is_offline(err: &::media::MediaError) -> bool {
+    match *err {
+        ::media::MediaError::Api(::api::ApiError::Io(_)) => true,
+        ::media::MediaError::Api(::api::ApiError::Hyper(_)) => true,
+        _ => false,
+    }
+}
+
+// post posts the entry of id queued by account and removes it from outbox, posted is called with each posted
+// tweet. when it fails, the error and the tweets posted so far are kept in the entry to resume the thread.
+// the entry is marked in flight while posting, and pasted images are removed after it is posted
+pub fn post<F: FnMut(TimelineRow)>(
+    client: &::api::Client,
+    outbox: &Mutex<Outbox>,
+    account: &str,
+    id: i64,
+    media_dir: &path::Path,
+    posted: F,
+) -> Result<(), ::media::MediaError> {
+    // outbox is not locked while posting, the gtk thread edits it meanwhile
+    let mut thread = {
+        let mut guard = lock(outbox);
+        match entries(&mut guard, account).iter_mut().find(|entry| entry.id == id) {
+            Some(entry) => {
+                if entry.in_flight {
+                    return Err(::media::MediaError::Unsupported("the tweet is being posted".to_owned()));
+                }
+                entry.in_flight = true;
+                entry.thread.clone()
+            },
+            None => return Err(::media::MediaError::Unsupported("the tweet is not queued".to_owned())),
+        }
+    };
+    let result = thread.post(client, |_| (), posted);
+
+    let mut guard = lock(outbox);
+    let entries = entries(&mut guard, account);
+    match result {
+        Ok(_) => {
+            entries.retain(|entry| entry.id != id);
+            for draft in &thread.drafts {
+                ::media::remove_pasted(&draft.media, media_dir);
+            }
+            Ok(())
+        },
+        Err(err) => {
+            if let Some(entry) = entries.iter_mut().find(|entry| entry.id == id) {
+                entry.thread = thread;
+                entry.in_flight = false;
+                entry.error = Some(::media::describe(&err));
+                entry.held = !is_offline(&err);
+            }
+            Err(err)
+        },
+    }
+}
+
+// drain posts due entries of account in order and returns the posted tweets, it stops when the network is down.
+// None is returned when no entry is due
+pub fn drain(
+    client: &::api::Client,
+    outbox: &Mutex<Outbox>,
+    account: &str,
+    media_dir: &path::Path,
+) -> Option<Vec<TimelineRow>> {
+    let now = Local::now().timestamp();
+    let ids: Vec<i64> = entries(&mut lock(outbox), account)
+        .iter()
+        .filter(|entry| entry.is_due(now))
+        .map(|entry| entry.id)
+        .collect();
+    if ids.is_empty() {
+        return None;
+    }
+    let mut posted = Vec::new();
+    for id in ids {
+        match post(client, outbox, account, id, media_dir, |row| posted.push(row)) {
+            Ok(_) => (),
+            Err(ref err) if is_offline(err) => {
+                error!("{:?}", err);
+                break;
+            },
+            Err(err) => error!("{:?}", err),
+        }
+    }
+    Some(posted)
+}
+
+// entries returns entries queued by account
+pub fn entries<'a>(outbox: &'a mut Outbox, account: &str) -> &'a mut Vec<Entry> {
+    outbox.entry(account.to_owned()).or_insert_with(Vec::new)
+}
+
+fn lock(outbox: &Mutex<Outbox>) -> ::std::sync::MutexGuard<Outbox> {
+    match outbox.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+// OutboxView shows queued and scheduled tweets with retry, edit and cancel buttons
+#[derive(Clone)]
+pub struct OutboxView {
+    pub container: gtk::ScrolledWindow,
+    pub outbox: Arc<Mutex<Outbox>>,
+    listbox: gtk::ListBox,
+    label: gtk::Label,
+    config: Arc<::config::Config>,
+    token: Arc<Mutex<egg_mode::Token<'static>>>,
+    cache_dir: path::PathBuf,
+    media_dir: path::PathBuf,
+    // posted is called with each tweet posted by retry, edit is called with the draft and the scheduled time
+    // of the edited entry, and returns true when it is sent again
+    posted: Rc<RefCell<Option<Rc<Fn(TimelineRow)>>>>,
+    edit: Rc<RefCell<Option<Rc<Fn(::compose::Draft, Option<i64>) -> bool>>>>,
+}
+
+impl OutboxView {
+    pub fn new(
+        entries: Vec<Entry>,
+        label: gtk::Label,
+        config: Arc<::config::Config>,
+        token: Arc<Mutex<egg_mode::Token<'static>>>,
+        cache_dir: path::PathBuf,
+    ) -> OutboxView {
+        let container = gtk::ScrolledWindow::new(None, None);
+        let listbox = gtk::ListBox::new();
+        listbox.set_selection_mode(gtk::SelectionMode::None);
+        container.add(&listbox);
+        let mut outbox = Outbox::new();
+        outbox.insert(config.account(), loaded(entries));
+
+        OutboxView {
+            container: container,
+            outbox: Arc::new(Mutex::new(outbox)),
+            listbox: listbox,
+            label: label,
+            config: config,
+            token: token,
+            media_dir: cache_dir.join(::vars::CACHE_MEDIA_DIR),
+            cache_dir: cache_dir,
+            posted: Rc::new(RefCell::new(None)),
+            edit: Rc::new(RefCell::new(None)),
+        }
+    }
+
+    pub fn connect_posted<F: Fn(TimelineRow) + 'static>(&self, f: F) {
+        *self.posted.borrow_mut() = Some(Rc::new(f));
+    }
+
+    pub fn connect_edit<F: Fn(::compose::Draft, Option<i64>) -> bool + 'static>(&self, f: F) {
+        *self.edit.borrow_mut() = Some(Rc::new(f));
+    }
+
+    // push queues thread to post at scheduled_at
+    pub fn push(&self, thread: ::compose::Thread, scheduled_at: Option<i64>) {
+        self.queue(Entry::new(thread, scheduled_at));
+    }
+
+    // hold keeps the thread left unfinished by error, it is posted by resume only
+    pub fn hold(&self, thread: ::compose::Thread, error: String) {
+        let mut entry = Entry::new(thread, None);
+        entry.error = Some(error);
+        entry.held = true;
+        self.queue(entry);
+    }
+
+    fn queue(&self, entry: Entry) {
+        let account = self.config.account();
+        entries(&mut lock(&self.outbox), &account).push(entry);
+        self.write(&account);
+        self.render();
+    }
+
+    // render rebuilds rows of entries and the side pane label with the number of them
+    pub fn render(&self) {
+        for widget in self.listbox.get_children() {
+            self.listbox.remove(&widget);
+        }
+        let account = self.config.account();
+        let entries = entries(&mut lock(&self.outbox), &account).clone();
+        match entries.len() {
+            0 => self.label.set_text("Outbox"),
+            count => self.label.set_text(&format!("Outbox ({})", count)),
+        }
+
+        for entry in entries {
+            let draft = entry.thread.draft();
+            let media: usize = entry.thread.drafts.iter().map(|draft| draft.media.len()).sum();
+            let label_text = gtk::Label::new(Some(draft.text.as_str()));
+            label_text.set_line_wrap(true);
+            label_text.set_xalign(0.0);
+            let label_status = gtk::Label::new(None);
+            label_status.set_markup(&format!("<small>{}</small>", ::timeline::utils::escape_markup(&entry.status())));
+            label_status.set_xalign(0.0);
+            if media > 0 {
+                label_text.set_text(&format!("{} [{} media]", draft.text, media));
+            }
+
+            let box_label = gtk::Box::new(gtk::Orientation::Vertical, 2);
+            box_label.pack_start(&label_text, true, true, 0);
+            box_label.pack_start(&label_status, false, false, 0);
+
+            // a partially posted thread is resumed, and it is not edited because posted tweets are not changed
+            let button_retry = match entry.thread.posted {
+                0 => gtk::Button::new_with_label("Retry"),
+                _ => gtk::Button::new_with_label("Resume"),
+            };
+            let button_edit = gtk::Button::new_with_label("Edit");
+            let button_cancel = gtk::Button::new_with_label("Cancel");
+            // the entry being posted by the update thread is not touched until it is done
+            button_retry.set_sensitive(!entry.in_flight);
+            button_edit.set_sensitive(!entry.in_flight && entry.thread.posted == 0);
+            button_cancel.set_sensitive(!entry.in_flight);
+
+            // event definition, when retry button is clicked
+            {
+                let view = self.clone();
+                let id = entry.id;
+
+                button_retry.connect_clicked(move |_| {
+                    let account = view.config.account();
+                    let client = ::api::client(&view.config, &view.token);
+                    let mut rows = Vec::new();
+                    let result = post(&client, &view.outbox, &account, id, &view.media_dir, |row| rows.push(row));
+                    view.write(&account);
+                    view.render();
+                    // the error is shown in the status of the entry
+                    if let Err(err) = result {
+                        error!("{:?}", err);
+                    }
+                    let posted = view.posted.borrow().clone();
+                    if let Some(posted) = posted {
+                        for row in rows {
+                            posted(row);
+                        }
+                    }
+                });
+            }
+
+            // event definition, when edit button is clicked, the entry is reopened by compose,
+            // and it is kept until the edited one is sent
+            {
+                let view = self.clone();
+                let id = entry.id;
+
+                button_edit.connect_clicked(move |_| {
+                    let edit = match view.edit.borrow().clone() {
+                        Some(edit) => edit,
+                        None => return,
+                    };
+                    if let Some(entry) = view.reopen(id) {
+                        let sent = edit(entry.thread.draft(), entry.scheduled_at);
+                        view.close(id, sent);
+                    }
+                });
+            }
+
+            // event definition, when cancel button is clicked
+            {
+                let view = self.clone();
+                let id = entry.id;
+
+                button_cancel.connect_clicked(move |_| {
+                    if let Some(thread) = view.remove(id) {
+                        for draft in &thread.drafts {
+                            ::media::remove_pasted(&draft.media, &view.media_dir);
+                        }
+                    }
+                });
+            }
+
+            let box_row = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+            box_row.set_border_width(6);
+            box_row.pack_start(&box_label, true, true, 0);
+            box_row.pack_start(&button_retry, false, false, 0);
+            box_row.pack_start(&button_edit, false, false, 0);
+            box_row.pack_start(&button_cancel, false, false, 0);
+
+            let row = gtk::ListBoxRow::new();
+            row.add(&box_row);
+            self.listbox.insert(&row, -1);
+        }
+        self.listbox.show_all();
+    }
+
+    // write writes the outbox of account to its cache
+    pub fn write(&self, account: &str) {
+        let cache = ::cache::path(&self.cache_dir, ::vars::CACHE_OUTBOX, account);
+        let entries = entries(&mut lock(&self.outbox), account).clone();
+        match ::cache::write(cache, &entries) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+    }
+
+    // switch writes the outbox of the previous account and loads the one of the switched account,
+    // the loaded outbox is kept because the update thread may still post entries of the previous account
+    pub fn switch(&self, from: &str) {
+        self.write(from);
+        let account = self.config.account();
+        if !lock(&self.outbox).contains_key(&account) {
+            let cache = ::cache::path(&self.cache_dir, ::vars::CACHE_OUTBOX, &account);
+            let entries = match ::cache::load(cache) {
+                Ok(entries) => entries,
+                Err(err) => {
+                    error!("{:?}", err);
+                    Vec::new()
+                },
+            };
+            lock(&self.outbox).insert(account, loaded(entries));
+        }
+        self.render();
+    }
+
+    // remove removes the entry of id and returns its thread, the entry being posted is not removed
+    fn remove(&self, id: i64) -> Option<::compose::Thread> {
+        let account = self.config.account();
+        let thread = {
+            let mut guard = lock(&self.outbox);
+            let entries = entries(&mut guard, &account);
+            match entries.iter().position(|entry| entry.id == id) {
+                Some(index) if !entries[index].in_flight => Some(entries.remove(index).thread),
+                _ => None,
+            }
+        };
+        self.write(&account);
+        self.render();
+        thread
+    }
+
+    // reopen marks the entry of id in flight while it is edited and returns it,
+    // the entry being posted and a partially posted thread are not edited
+    fn reopen(&self, id: i64) -> Option<Entry> {
+        let account = self.config.account();
+        let entry = {
+            let mut guard = lock(&self.outbox);
+            match entries(&mut guard, &account).iter_mut().find(|entry| entry.id == id) {
+                Some(entry) if !entry.in_flight && entry.thread.posted == 0 => {
+                    entry.in_flight = true;
+                    Some(entry.clone())
+                },
+                _ => None,
+            }
+        };
+        self.render();
+        entry
+    }
+
+    // close removes the reopened entry of id when its edit is sent, or queues it again as it was
+    fn close(&self, id: i64, sent: bool) {
+        let account = self.config.account();
+        {
+            let mut guard = lock(&self.outbox);
+            let entries = entries(&mut guard, &account);
+            match sent {
+                true => entries.retain(|entry| entry.id != id),
+                false => for entry in entries.iter_mut().filter(|entry| entry.id == id) {
+                    entry.in_flight = false;
+                },
+            }
+        }
+        self.write(&account);
+        self.render();
+    }
+}
+
+// loaded returns entries read from the cache, none of them is posted yet
+fn loaded(entries: Vec<Entry>) -> Vec<Entry> {
+    entries.into_iter().map(|entry| Entry { in_flight: false, ..entry }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(scheduled_at: Option<i64>) -> Entry {
+        let draft = ::compose::Draft {
+            text: "queued".to_owned(),
+            ..::compose::Draft::default()
+        };
+        Entry::new(::compose::Thread::single(draft), scheduled_at)
+    }
+
+    #[test]
+    fn is_due_at_scheduled_time() {
+        assert!(entry(None).is_due(0));
+        assert!(!entry(Some(100)).is_due(99));
+        assert!(entry(Some(100)).is_due(100));
+        assert!(entry(Some(100)).is_due(101));
+    }
+
+    #[test]
+    fn is_due_skips_held_and_in_flight() {
+        let held = Entry {
+            held: true,
+            ..entry(None)
+        };
+        let in_flight = Entry {
+            in_flight: true,
+            ..entry(None)
+        };
+        assert!(!held.is_due(0));
+        assert!(!in_flight.is_due(0));
+    }
+
+    #[test]
+    fn loaded_clears_in_flight() {
+        let in_flight = Entry {
+            in_flight: true,
+            held: true,
+            ..entry(Some(100))
+        };
+        let entries = loaded(vec![in_flight, entry(None)]);
+        assert!(entries.iter().all(|entry| !entry.in_flight));
+        assert!(entries[0].held);
+        assert_eq!(entries[0].scheduled_at, Some(100));
+    }
+}
@@ -0,0 +1,286 @@
+extern crate chrono;
+extern crate egg_mode;
+
+use chrono::{Local, TimeZone};
+use gtk;
+use gtk::prelude::*;
+use std::path;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+// MUTE_EXPIRY is the choice of durations which a mute rule expires after, in seconds
+const MUTE_EXPIRY: [(&'static str, Option<i64>); 5] = [
+    ("Forever", None),
+    ("1 hour", Some(60 * 60)),
+    ("1 day", Some(24 * 60 * 60)),
+    ("7 days", Some(7 * 24 * 60 * 60)),
+    ("30 days", Some(30 * 24 * 60 * 60)),
+];
+
+// show runs the preferences dialog until it is closed, the mute list is saved to config when it is closed
+// and mute rules are saved when they are added or removed
+pub fn show(parent: &gtk::Window, config: Arc<::config::Config>, token: Arc<Mutex<egg_mode::Token<'static>>>) {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("preferences"),
+        Some(parent),
+        gtk::DIALOG_MODAL | gtk::DIALOG_DESTROY_WITH_PARENT,
+        &[("Close", gtk::ResponseType::Close.into())],
+    );
+    let content_area = dialog.get_content_area();
+    content_area.set_spacing(6);
+    content_area.set_border_width(12);
+
+    // account setting
+    let frame_account = gtk::Frame::new(Some("Account"));
+    let box_account = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    box_account.set_border_width(6);
+
+    let label_status = gtk::Label::new(None);
+    label_status.set_xalign(0.0);
+    set_account_status(&label_status, &config);
+
+    let button_authorize = gtk::Button::new_with_label("Re-authorize");
+    {
+        let parent = parent.clone();
+        let config = config.clone();
+        let label_status = label_status.clone();
+
+        button_authorize.connect_clicked(move |_| {
+            reauthorize(&parent, &config, &token);
+            set_account_status(&label_status, &config);
+        });
+    }
+
+    box_account.pack_start(&label_status, true, true, 0);
+    box_account.pack_start(&button_authorize, false, false, 0);
+    frame_account.add(&box_account);
+    content_area.pack_start(&frame_account, false, false, 0);
+
+    // credential store setting
+    let frame_secrets = gtk::Frame::new(Some("Credentials"));
+    let box_secrets = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    box_secrets.set_border_width(6);
+
+    let label_secrets = gtk::Label::new(None);
+    label_secrets.set_xalign(0.0);
+    set_secrets_status(&label_secrets, &config);
+
+    let chooser_key_file = gtk::FileChooserButton::new("key file", gtk::FileChooserAction::Open);
+    let button_encrypt = gtk::Button::new_with_label("Encrypt");
+    button_encrypt.set_sensitive(config.store.borrow().is_none());
+    {
+        let parent = parent.clone();
+        let config = config.clone();
+        let label_secrets = label_secrets.clone();
+        let chooser_key_file = chooser_key_file.clone();
+
+        button_encrypt.connect_clicked(move |button| {
+            let key_file = chooser_key_file.get_filename().and_then(|path| path.to_str().map(|s| s.to_owned()));
+            match encrypt_credentials(&parent, &config, key_file) {
+                Ok(_) => button.set_sensitive(false),
+                Err(::config::ConfigError::Secrets(::secrets::SecretsError::Cancel)) => (),
+                Err(err) => error!("{:?}", err),
+            }
+            set_secrets_status(&label_secrets, &config);
+        });
+    }
+
+    box_secrets.pack_start(&label_secrets, true, true, 0);
+    box_secrets.pack_start(&chooser_key_file, false, false, 0);
+    box_secrets.pack_start(&button_encrypt, false, false, 0);
+    frame_secrets.add(&box_secrets);
+    content_area.pack_start(&frame_secrets, false, false, 0);
+
+    // mute setting, an entry is a user id or a screen name per line
+    let frame_muted = gtk::Frame::new(Some("Muted users"));
+    let scrolled_muted = gtk::ScrolledWindow::new(None, None);
+    scrolled_muted.set_border_width(6);
+    scrolled_muted.set_size_request(-1, 120);
+    let text_view_muted = gtk::TextView::new();
+    text_view_muted.set_tooltip_text(Some("A screen name or id: and a user id per line"));
+    let buffer_muted = text_view_muted.get_buffer();
+    if let Some(ref buffer) = buffer_muted {
+        buffer.set_text(&config.muted().join("\n"));
+    }
+    scrolled_muted.add(&text_view_muted);
+    frame_muted.add(&scrolled_muted);
+    content_area.pack_start(&frame_muted, true, true, 0);
+
+    // mute rule setting, tweets which match a rule are collapsed in timelines
+    let frame_rules = gtk::Frame::new(Some("Mute rules"));
+    let box_rules = gtk::Box::new(gtk::Orientation::Vertical, 4);
+    box_rules.set_border_width(6);
+
+    let listbox_rules = gtk::ListBox::new();
+    listbox_rules.set_selection_mode(gtk::SelectionMode::None);
+    render_mute_rules(&listbox_rules, config.clone());
+
+    let entry_pattern = gtk::Entry::new();
+    entry_pattern.set_placeholder_text(Some("word, phrase or regex"));
+    let combo_expiry = gtk::ComboBoxText::new();
+    for &(label, _) in MUTE_EXPIRY.iter() {
+        combo_expiry.append_text(label);
+    }
+    combo_expiry.set_active(0);
+    let button_add = gtk::Button::new_with_label("Add");
+    let box_pattern = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    box_pattern.pack_start(&entry_pattern, true, true, 0);
+    box_pattern.pack_start(&combo_expiry, false, false, 0);
+    box_pattern.pack_start(&button_add, false, false, 0);
+
+    let check_regex = gtk::CheckButton::new_with_label("Regex");
+    let check_retweets = gtk::CheckButton::new_with_label("Retweets only");
+    let check_non_followed = gtk::CheckButton::new_with_label("Users not followed only");
+    let label_rule_error = gtk::Label::new(None);
+    label_rule_error.set_xalign(0.0);
+    let box_options = gtk::Box::new(gtk::Orientation::Horizontal, 6);
+    box_options.pack_start(&check_regex, false, false, 0);
+    box_options.pack_start(&check_retweets, false, false, 0);
+    box_options.pack_start(&check_non_followed, false, false, 0);
+    box_options.pack_start(&label_rule_error, true, true, 0);
+
+    // event definition, when add button is clicked or pattern entry is activated
+    let add_rule = {
+        let config = config.clone();
+        let listbox_rules = listbox_rules.clone();
+        let entry_pattern = entry_pattern.clone();
+
+        Rc::new(move || {
+            let pattern = entry_pattern.get_text().unwrap_or(String::new());
+            if pattern.trim().is_empty() {
+                return;
+            }
+            let expiry = MUTE_EXPIRY.get(combo_expiry.get_active() as usize).and_then(|&(_, expiry)| expiry);
+            let rule = ::config::MuteRule {
+                pattern: pattern.trim().to_owned(),
+                regex: check_regex.get_active(),
+                expires_at: expiry.map(|expiry| Local::now().timestamp() + expiry),
+                retweets_only: check_retweets.get_active(),
+                non_followed_only: check_non_followed.get_active(),
+            };
+            match ::timeline::utils::mute_regex(&rule) {
+                Ok(_) => {
+                    config.add_mute_rule(rule);
+                    entry_pattern.set_text("");
+                    label_rule_error.set_text("");
+                    render_mute_rules(&listbox_rules, config.clone());
+                },
+                Err(err) => label_rule_error.set_text(&format!("invalid regex: {}", err)),
+            }
+        })
+    };
+    {
+        let add_rule = add_rule.clone();
+        entry_pattern.connect_activate(move |_| add_rule());
+    }
+    button_add.connect_clicked(move |_| add_rule());
+
+    box_rules.pack_start(&listbox_rules, false, false, 0);
+    box_rules.pack_start(&box_pattern, false, false, 0);
+    box_rules.pack_start(&box_options, false, false, 0);
+    frame_rules.add(&box_rules);
+    content_area.pack_start(&frame_rules, false, false, 0);
+
+    dialog.show_all();
+    dialog.run();
+    if let Some(buffer) = buffer_muted {
+        let (start, end) = buffer.get_bounds();
+        let text = buffer.get_text(&start, &end, false).unwrap_or(String::new());
+        config.set_muted(text.lines().map(|line| line.to_owned()).collect());
+    }
+    dialog.destroy();
+}
+
+// render_mute_rules rebuilds rows of mute rules with their remove buttons
+fn render_mute_rules(listbox: &gtk::ListBox, config: Arc<::config::Config>) {
+    for widget in listbox.get_children() {
+        listbox.remove(&widget);
+    }
+    for (index, rule) in config.mute_rules().iter().enumerate() {
+        let expiry = match rule.expires_at {
+            Some(expires_at) => format!(" until {}", Local.timestamp(expires_at, 0).format(::compose::SCHEDULE_FORMAT)),
+            None => String::new(),
+        };
+        let label = gtk::Label::new(Some(
+            format!("{}{}", ::timeline::utils::describe_mute_rule(rule), expiry).as_str(),
+        ));
+        label.set_xalign(0.0);
+        let button_remove = gtk::Button::new_from_icon_name("list-remove", 1);
+        button_remove.set_relief(gtk::ReliefStyle::None);
+        {
+            let listbox = listbox.clone();
+            let config = config.clone();
+
+            button_remove.connect_clicked(move |_| {
+                config.remove_mute_rule(index);
+                render_mute_rules(&listbox, config.clone());
+            });
+        }
+
+        let box_row = gtk::Box::new(gtk::Orientation::Horizontal, 2);
+        box_row.pack_start(&label, true, true, 0);
+        box_row.pack_start(&button_remove, false, false, 0);
+        let row = gtk::ListBoxRow::new();
+        row.add(&box_row);
+        listbox.insert(&row, -1);
+    }
+    listbox.show_all();
+}
+
+// reauthorize replaces the shared token with a newly authorized one
+pub fn reauthorize(parent: &gtk::Window, config: &::config::Config, token: &Mutex<egg_mode::Token<'static>>) {
+    let access = match ::auth::authorize_dialog(Some(parent), config) {
+        Ok((access, _)) => access,
+        Err(::auth::AuthError::Cancel) => return,
+        Err(err) => {
+            error!("{:?}", err);
+            return;
+        },
+    };
+    match ::auth::save_token(config, &access) {
+        Ok(_) => (),
+        Err(err) => {
+            error!("{:?}", err);
+            return;
+        },
+    }
+    let mut guard = match token.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *guard = access;
+}
+
+// encrypt_credentials migrates plaintext access keys in config to the encrypted credential store
+fn encrypt_credentials(
+    parent: &gtk::Window,
+    config: &::config::Config,
+    key_file: Option<String>,
+) -> Result<(), ::config::ConfigError> {
+    let passphrase = match key_file {
+        Some(ref key_file) => try!(::secrets::read_key_file(key_file)),
+        None => try!(::secrets::passphrase_dialog(Some(parent), true)),
+    };
+    let filename = match path::Path::new(&config.filename).parent() {
+        Some(config_dir) => config_dir.join(::vars::SECRETS),
+        None => return Err(::config::ConfigError::String("config directory is not found".to_owned())),
+    };
+    let store = try!(::secrets::Store::create(filename, &passphrase));
+    config.migrate_secrets(store, key_file)
+}
+
+fn set_secrets_status(label: &gtk::Label, config: &::config::Config) {
+    if config.store.borrow().is_some() {
+        label.set_text("encrypted");
+    } else {
+        label.set_text("plaintext in config.toml");
+    }
+}
+
+fn set_account_status(label: &gtk::Label, config: &::config::Config) {
+    if config.is_authorized() {
+        label.set_text("authorized");
+    } else {
+        label.set_text("not authorized");
+    }
+}
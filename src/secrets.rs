@@ -0,0 +1,325 @@
+extern crate crypto;
+extern crate rustc_serialize;
+
+use crypto::aead::{AeadDecryptor, AeadEncryptor};
+use crypto::chacha20poly1305::ChaCha20Poly1305;
+use crypto::hmac::Hmac;
+use crypto::pbkdf2::pbkdf2;
+use crypto::sha2::Sha256;
+use gtk;
+use gtk::prelude::*;
+use rustc_serialize::hex::{FromHex, FromHexError, ToHex};
+use rustc_serialize::json;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::io::prelude::*;
+use std::path;
+use std::string;
+
+// PBKDF2_ROUNDS is iteration count to derive the encryption key from the passphrase
+const PBKDF2_ROUNDS: u32 = 100000;
+// CONSUMER is the entry name of the consumer key pair
+const CONSUMER: &'static str = "consumer";
+
+// SecretsError
+#[derive(Debug)]
+pub enum SecretsError {
+    Io(io::Error),
+    JsonEncoder(json::EncoderError),
+    JsonDecoder(json::DecoderError),
+    FromHex(FromHexError),
+    FromUtf8(string::FromUtf8Error),
+    Path(path::PathBuf),
+    Decrypt,
+    Cancel,
+}
+
+impl From<io::Error> for SecretsError {
+    fn from(err: io::Error) -> SecretsError {
+        SecretsError::Io(err)
+    }
+}
+
+impl From<json::EncoderError> for SecretsError {
+    fn from(err: json::EncoderError) -> SecretsError {
+        SecretsError::JsonEncoder(err)
+    }
+}
+
+impl From<json::DecoderError> for SecretsError {
+    fn from(err: json::DecoderError) -> SecretsError {
+        SecretsError::JsonDecoder(err)
+    }
+}
+
+impl From<FromHexError> for SecretsError {
+    fn from(err: FromHexError) -> SecretsError {
+        SecretsError::FromHex(err)
+    }
+}
+
+impl From<string::FromUtf8Error> for SecretsError {
+    fn from(err: string::FromUtf8Error) -> SecretsError {
+        SecretsError::FromUtf8(err)
+    }
+}
+
+// Sealed is the encrypted secrets file format
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+struct Sealed {
+    salt: String,
+    nonce: String,
+    tag: String,
+    data: String,
+}
+
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct KeyPair {
+    pub key: String,
+    pub secret: String,
+}
+
+// Store holds the decrypted key pairs by name, the key of an account is stored by the account name
+#[derive(Clone, Debug)]
+pub struct Store {
+    filename: String,
+    salt: Vec<u8>,
+    key: Vec<u8>,
+    entries: RefCell<BTreeMap<String, KeyPair>>,
+}
+
+impl Store {
+    // create returns the empty store which is written to filename on save
+    pub fn create<P: AsRef<path::Path>>(filename: P, passphrase: &str) -> Result<Store, SecretsError> {
+        let filename = try!(path_string(filename.as_ref()));
+        let salt = try!(random_bytes(16));
+        let key = derive_key(passphrase, &salt);
+        Ok(Store {
+            filename: filename,
+            salt: salt,
+            key: key,
+            entries: RefCell::new(BTreeMap::new()),
+        })
+    }
+
+    // open decrypts the store of filename by passphrase
+    pub fn open<P: AsRef<path::Path>>(filename: P, passphrase: &str) -> Result<Store, SecretsError> {
+        let mut body = String::new();
+        try!(try!(File::open(filename.as_ref())).read_to_string(&mut body));
+        let sealed: Sealed = try!(json::decode(&body));
+
+        let salt = try!(sealed.salt.from_hex());
+        let nonce = try!(sealed.nonce.from_hex());
+        let tag = try!(sealed.tag.from_hex());
+        let data = try!(sealed.data.from_hex());
+        let key = derive_key(passphrase, &salt);
+
+        let mut plain = vec![0u8; data.len()];
+        let mut cipher = ChaCha20Poly1305::new(&key, &nonce, &[]);
+        if !cipher.decrypt(&data, &mut plain, &tag) {
+            return Err(SecretsError::Decrypt);
+        }
+        let entries: BTreeMap<String, KeyPair> = try!(json::decode(&try!(String::from_utf8(plain))));
+
+        let filename = try!(path_string(filename.as_ref()));
+        Ok(Store {
+            filename: filename,
+            salt: salt,
+            key: key,
+            entries: RefCell::new(entries),
+        })
+    }
+
+    // save encrypts entries with a fresh nonce and writes them to the store file
+    pub fn save(&self) -> Result<(), SecretsError> {
+        let plain = try!(json::encode(&*self.entries.borrow()));
+        let nonce = try!(random_bytes(8));
+
+        let mut data = vec![0u8; plain.len()];
+        let mut tag = [0u8; 16];
+        let mut cipher = ChaCha20Poly1305::new(&self.key, &nonce, &[]);
+        cipher.encrypt(plain.as_bytes(), &mut data, &mut tag);
+
+        let sealed = Sealed {
+            salt: self.salt.to_hex(),
+            nonce: nonce.to_hex(),
+            tag: tag.to_hex(),
+            data: data.to_hex(),
+        };
+        let mut file = try!(File::create(&self.filename));
+        try!(file.write_all(try!(json::encode(&sealed)).as_bytes()));
+        try!(file.sync_all());
+        info!("secrets are synchronized");
+        Ok(())
+    }
+
+    pub fn filename(&self) -> &str {
+        &self.filename
+    }
+
+    pub fn get(&self, name: &str) -> Option<KeyPair> {
+        self.entries.borrow().get(name).cloned()
+    }
+
+    pub fn set(&self, name: &str, key: String, secret: String) {
+        self.entries.borrow_mut().insert(
+            name.to_owned(),
+            KeyPair {
+                key: key,
+                secret: secret,
+            },
+        );
+    }
+
+    pub fn consumer(&self) -> Option<KeyPair> {
+        self.get(CONSUMER)
+    }
+
+    pub fn set_consumer(&self, key: String, secret: String) {
+        self.set(CONSUMER, key, secret)
+    }
+}
+
+// path_string returns filename as string, the store file name is kept in config.toml
+fn path_string(filename: &path::Path) -> Result<String, SecretsError> {
+    match filename.to_str() {
+        Some(filename) => Ok(filename.to_owned()),
+        None => Err(SecretsError::Path(filename.to_path_buf())),
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::new(Sha256::new(), passphrase.as_bytes());
+    let mut key = vec![0u8; 32];
+    pbkdf2(&mut mac, salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+fn random_bytes(len: usize) -> Result<Vec<u8>, SecretsError> {
+    let mut bytes = vec![0u8; len];
+    try!(try!(File::open("/dev/urandom")).read_exact(&mut bytes));
+    Ok(bytes)
+}
+
+// read_key_file returns the passphrase made from the content of key file
+pub fn read_key_file<P: AsRef<path::Path>>(filename: P) -> Result<String, SecretsError> {
+    let mut body = Vec::new();
+    try!(try!(File::open(filename)).read_to_end(&mut body));
+    Ok(body.to_hex())
+}
+
+// passphrase_dialog asks the passphrase, and asks it twice when confirm is true
+pub fn passphrase_dialog(parent: Option<&gtk::Window>, confirm: bool) -> Result<String, SecretsError> {
+    let dialog = gtk::Dialog::new_with_buttons(
+        Some("rustytwit secrets"),
+        parent,
+        gtk::DIALOG_MODAL | gtk::DIALOG_DESTROY_WITH_PARENT,
+        &[
+            ("Cancel", gtk::ResponseType::Cancel.into()),
+            ("Unlock", gtk::ResponseType::Ok.into()),
+        ],
+    );
+    dialog.set_default_response(gtk::ResponseType::Ok.into());
+
+    let content_area = dialog.get_content_area();
+    content_area.set_spacing(6);
+    content_area.set_border_width(12);
+
+    let label_description = gtk::Label::new(Some("Enter the passphrase of the credential store."));
+    label_description.set_xalign(0.0);
+
+    let entry_passphrase = gtk::Entry::new();
+    entry_passphrase.set_visibility(false);
+    entry_passphrase.set_activates_default(true);
+
+    let entry_confirm = gtk::Entry::new();
+    entry_confirm.set_visibility(false);
+    entry_confirm.set_activates_default(true);
+    entry_confirm.set_placeholder_text(Some("confirm passphrase"));
+
+    content_area.pack_start(&label_description, false, false, 0);
+    content_area.pack_start(&entry_passphrase, false, false, 0);
+    if confirm {
+        content_area.pack_start(&entry_confirm, false, false, 0);
+    }
+    dialog.show_all();
+
+    loop {
+        let response = dialog.run();
+        if response != gtk::ResponseType::Ok.into() {
+            dialog.destroy();
+            return Err(SecretsError::Cancel);
+        }
+        let passphrase = entry_passphrase.get_text().unwrap_or(String::new());
+        if passphrase.is_empty() {
+            continue;
+        }
+        if confirm && entry_confirm.get_text().unwrap_or(String::new()) != passphrase {
+            label_description.set_text("Passphrases do not match.");
+            continue;
+        }
+        dialog.destroy();
+        return Ok(passphrase);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn store_file(name: &str) -> path::PathBuf {
+        env::temp_dir().join(format!("rustytwit-test-{}.json", name))
+    }
+
+    #[test]
+    fn save_and_open_round_trip() {
+        let filename = store_file("round-trip");
+        let store = Store::create(&filename, "passphrase").unwrap();
+        store.set("alice", "access key".to_owned(), "access secret".to_owned());
+        store.set_consumer("consumer key".to_owned(), "consumer secret".to_owned());
+        store.save().unwrap();
+
+        let opened = Store::open(&filename, "passphrase").unwrap();
+        fs::remove_file(&filename).unwrap();
+        let access = opened.get("alice").unwrap();
+        assert_eq!(access.key, "access key");
+        assert_eq!(access.secret, "access secret");
+        let consumer = opened.consumer().unwrap();
+        assert_eq!(consumer.key, "consumer key");
+        assert_eq!(consumer.secret, "consumer secret");
+        assert!(opened.get("bob").is_none());
+        assert_eq!(opened.filename(), filename.to_str().unwrap());
+    }
+
+    #[test]
+    fn open_with_wrong_passphrase_fails() {
+        let filename = store_file("wrong-passphrase");
+        let store = Store::create(&filename, "passphrase").unwrap();
+        store.set("alice", "access key".to_owned(), "access secret".to_owned());
+        store.save().unwrap();
+
+        let result = Store::open(&filename, "wrong");
+        fs::remove_file(&filename).unwrap();
+        match result {
+            Err(SecretsError::Decrypt) => (),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sealed_file_has_no_plain_secret() {
+        let filename = store_file("sealed");
+        let store = Store::create(&filename, "passphrase").unwrap();
+        store.set("alice", "access key".to_owned(), "access secret".to_owned());
+        store.save().unwrap();
+
+        let mut body = String::new();
+        File::open(&filename).unwrap().read_to_string(&mut body).unwrap();
+        fs::remove_file(&filename).unwrap();
+        assert!(!body.contains("access secret"));
+    }
+}
@@ -0,0 +1,77 @@
+extern crate rustc_serialize;
+
+use std::collections::BTreeMap;
+use timeline::home::{Engagement, TimelineRow};
+use timeline::utils::Action;
+
+// run requests retweet, unretweet, like or unlike of the tweet of id, and returns its engagement after it.
+// retweeted and favorited of the response may not be updated yet, they are set by the action
+pub fn run(client: &::api::Client, action: Action, id: u64) -> Result<Engagement, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    let path = match action {
+        Action::Retweet => format!("statuses/retweet/{}.json", id),
+        Action::Unretweet => format!("statuses/unretweet/{}.json", id),
+        Action::Like | Action::Unlike => {
+            params.insert("id".to_owned(), format!("{}", id));
+            match action {
+                Action::Like => "favorites/create.json".to_owned(),
+                _ => "favorites/destroy.json".to_owned(),
+            }
+        },
+        Action::Reply | Action::Quote | Action::Delete => {
+            return Err(::api::ApiError::InvalidResponse(format!("{:?} is not an engagement", action)))
+        },
+    };
+    let response = try!(client.post(&path, &params));
+    // the response of retweet is the retweet of the original tweet
+    let status = response.find("retweeted_status").cloned().unwrap_or(response.clone());
+    let mut engagement = Engagement::from_json(&status);
+    match action {
+        Action::Retweet => engagement.retweeted = true,
+        Action::Unretweet => engagement.retweeted = false,
+        Action::Like => engagement.favorited = true,
+        Action::Unlike => engagement.favorited = false,
+        Action::Reply | Action::Quote | Action::Delete => (),
+    }
+    Ok(engagement)
+}
+
+// update sets engagement to rows of the original tweet of id
+pub fn update(timeline: &mut Vec<TimelineRow>, id: u64, engagement: &Engagement) {
+    for row in timeline.iter_mut().filter(|row| row.tweet.status_id() == id) {
+        row.tweet.engagement = Some(engagement.clone());
+    }
+}
+
+// status_url returns the url of the tweet which is attached to a quote
+pub fn status_url(screen_name: &str, id: u64) -> String {
+    format!("https://twitter.com/{}/status/{}", screen_name, id)
+}
+
+// destroy requests deletion of the own tweet of id
+pub fn destroy(client: &::api::Client, id: u64) -> Result<(), ::api::ApiError> {
+    try!(client.post(&format!("statuses/destroy/{}.json", id), &BTreeMap::new()));
+    Ok(())
+}
+
+// remove removes rows of the tweet of id from timeline, and returns them with their indices for restore
+pub fn remove(timeline: &mut Vec<TimelineRow>, id: u64) -> Vec<(usize, TimelineRow)> {
+    let mut removed = Vec::new();
+    let mut index = 0;
+    while index < timeline.len() {
+        if timeline[index].tweet.id == id {
+            removed.push((index + removed.len(), timeline.remove(index)));
+        } else {
+            index += 1;
+        }
+    }
+    removed
+}
+
+// restore inserts rows removed by remove at their indices
+pub fn restore(timeline: &mut Vec<TimelineRow>, removed: &Vec<(usize, TimelineRow)>) {
+    for &(index, ref row) in removed {
+        let index = if index < timeline.len() { index } else { timeline.len() };
+        timeline.insert(index, row.clone());
+    }
+}
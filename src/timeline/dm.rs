@@ -0,0 +1,406 @@
+extern crate egg_mode;
+extern crate rustc_serialize;
+
+use gtk;
+use gtk::prelude::*;
+use rustc_serialize::json;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use timeline::home::{Tweet, TimelineRow, User};
+
+// Conversation is direct messages exchanged with a user, the newest message is first
+#[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
+pub struct Conversation {
+    pub screen_name: String,
+    pub messages: Vec<TimelineRow>,
+}
+
+// direct_messages requests received and sent messages, and groups them into conversations ordered by the newest
+pub fn direct_messages(
+    client: &::api::Client,
+    since_id: Option<u64>,
+    count: i32,
+) -> Result<Vec<Conversation>, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("count".to_owned(), format!("{}", count));
+    params.insert("tweet_mode".to_owned(), "extended".to_owned());
+    if let Some(since_id) = since_id {
+        params.insert("since_id".to_owned(), format!("{}", since_id));
+    }
+
+    let mut messages: Vec<(String, TimelineRow)> = Vec::new();
+    for &(path, sent) in &[("direct_messages.json", false), ("direct_messages/sent.json", true)] {
+        let response = try!(client.get(path, &params));
+        for message in try!(response.as_array().ok_or(format!("{} is not an array", path))) {
+            messages.push(try!(message_from_json(message, sent)));
+            debug!("{:?}", message);
+        }
+    }
+    messages.sort_by(|a, b| b.1.tweet.id.cmp(&a.1.tweet.id));
+
+    let mut conversations: Vec<Conversation> = Vec::new();
+    for (screen_name, row) in messages {
+        match conversations.iter().position(|conversation| conversation.screen_name == screen_name) {
+            Some(index) => conversations[index].messages.push(row),
+            None => {
+                conversations.push(Conversation {
+                    screen_name: screen_name,
+                    messages: vec![row],
+                })
+            },
+        }
+    }
+    Ok(conversations)
+}
+
+// send_message sends text to screen_name and returns the sent message as a read row
+pub fn send_message(client: &::api::Client, screen_name: &str, text: &str) -> Result<TimelineRow, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("screen_name".to_owned(), screen_name.to_owned());
+    params.insert("text".to_owned(), text.to_owned());
+    let message = try!(client.post("direct_messages/new.json", &params));
+    let (_, row) = try!(message_from_json(&message, true));
+    Ok(row)
+}
+
+// message_from_json converts a direct message object into a row with the screen name of the partner,
+// sent messages are already read
+fn message_from_json(message: &json::Json, sent: bool) -> Result<(String, TimelineRow), ::api::ApiError> {
+    let sender = try!(User::from_json(try!(::timeline::home::field(message, "sender"))));
+    let recipient = try!(User::from_json(try!(::timeline::home::field(message, "recipient"))));
+    let created_at = try!(::timeline::home::created_at_local(
+        &try!(::timeline::home::string_field(message, "created_at")),
+    ));
+    let id = try!(::timeline::home::field(message, "id").and_then(|v| {
+        v.as_u64().ok_or("id is not a number".to_owned())
+    }));
+    let partner = match sent {
+        true => recipient.screen_name.clone(),
+        false => sender.screen_name.clone(),
+    };
+    let row = TimelineRow {
+        tweet: Tweet {
+            created_at: created_at,
+            id: id,
+            text: try!(::timeline::home::status_text(message)),
+            attr: format!("@{} --> @{}", sender.screen_name, recipient.screen_name),
+            user: sender,
+            in_reply_to_status_id: None,
+            quoted_status: None,
+            retweeted_status: None,
+            engagement: None,
+        },
+        unread: !sent,
+        gap: None,
+    };
+    Ok((partner, row))
+}
+
+// merge prepends rows which are not in the conversation with screen_name, moves the conversation to the top,
+// and returns the added rows
+pub fn merge(
+    conversations: &mut Vec<Conversation>,
+    screen_name: &str,
+    rows: &Vec<TimelineRow>,
+    limit: usize,
+) -> Vec<TimelineRow> {
+    let index = conversations.iter().position(|conversation| conversation.screen_name == screen_name);
+    let mut conversation = match index {
+        Some(index) => conversations.remove(index),
+        None => {
+            Conversation {
+                screen_name: screen_name.to_owned(),
+                messages: Vec::new(),
+            }
+        },
+    };
+    let added: Vec<TimelineRow> = rows.iter()
+        .filter(|row| !conversation.messages.iter().any(|message| message.tweet.id == row.tweet.id))
+        .cloned()
+        .collect();
+    ::timeline::utils::prepend(&mut conversation.messages, &added, limit);
+
+    match index {
+        Some(index) if added.is_empty() => conversations.insert(index, conversation),
+        _ if conversation.messages.is_empty() => (),
+        _ => conversations.insert(0, conversation),
+    }
+    added
+}
+
+// latest_id returns the id of the newest message in conversations
+pub fn latest_id(conversations: &Vec<Conversation>) -> Option<u64> {
+    conversations
+        .iter()
+        .filter_map(|conversation| conversation.messages.first().map(|row| row.tweet.id))
+        .max()
+}
+
+// unread_label returns the label of side pane with the number of unread messages
+pub fn unread_label(name: &str, conversations: &Vec<Conversation>) -> String {
+    let count: usize = conversations
+        .iter()
+        .map(|conversation| ::timeline::utils::unread_count(&conversation.messages))
+        .sum();
+    match count {
+        0 => name.to_owned(),
+        count => format!("{} ({})", name, count),
+    }
+}
+
+// DirectMessagesView shows the conversation list and the messages of the selected conversation with a reply entry
+#[derive(Clone)]
+pub struct DirectMessagesView {
+    pub container: gtk::Paned,
+    pub conversations: Arc<Mutex<Vec<Conversation>>>,
+    conversation_listbox: gtk::ListBox,
+    listbox: gtk::ListBox,
+    entry_reply: gtk::Entry,
+    // label of the side pane row, it shows the number of unread messages
+    label: gtk::Label,
+    // screen name of the selected conversation
+    selected: Rc<RefCell<Option<String>>>,
+    config: Arc<::config::Config>,
+}
+
+impl DirectMessagesView {
+    pub fn new(
+        conversations: Vec<Conversation>,
+        label: gtk::Label,
+        config: Arc<::config::Config>,
+        token: Arc<Mutex<egg_mode::Token<'static>>>,
+        errorbar: ::errorbar::ErrorBar,
+    ) -> DirectMessagesView {
+        let container = gtk::Paned::new(gtk::Orientation::Horizontal);
+
+        let conversation_scrolled_window = gtk::ScrolledWindow::new(None, None);
+        let conversation_listbox = gtk::ListBox::new();
+        conversation_scrolled_window.add(&conversation_listbox);
+
+        let box_thread = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        let scrolled_window = gtk::ScrolledWindow::new(None, None);
+        let listbox = gtk::ListBox::new();
+        scrolled_window.add(&listbox);
+
+        let box_reply = gtk::Box::new(gtk::Orientation::Horizontal, 2);
+        box_reply.set_border_width(4);
+        let entry_reply = gtk::Entry::new();
+        entry_reply.set_placeholder_text(Some("reply"));
+        entry_reply.set_sensitive(false);
+        let button_send = gtk::Button::new_with_label("Send");
+        box_reply.pack_start(&entry_reply, true, true, 0);
+        box_reply.pack_start(&button_send, false, false, 0);
+
+        box_thread.pack_start(&scrolled_window, true, true, 0);
+        box_thread.pack_start(&box_reply, false, false, 0);
+
+        container.pack1(&conversation_scrolled_window, false, true);
+        container.pack2(&box_thread, true, true);
+
+        let view = DirectMessagesView {
+            container: container,
+            conversations: Arc::new(Mutex::new(conversations)),
+            conversation_listbox: conversation_listbox,
+            listbox: listbox,
+            entry_reply: entry_reply.clone(),
+            label: label,
+            selected: Rc::new(RefCell::new(None)),
+            config: config.clone(),
+        };
+
+        // event definition, when conversation is selected
+        {
+            let view = view.clone();
+
+            view.conversation_listbox.clone().connect_row_selected(move |_, listboxrow| {
+                *view.selected.borrow_mut() = listboxrow.clone().and_then(|listboxrow| listboxrow.get_name());
+                view.render_thread();
+            });
+        }
+
+        // event definition, when message is selected
+        {
+            let view = view.clone();
+
+            view.listbox.clone().connect_row_selected(move |_, listboxrow| {
+                let selected = match view.selected.borrow().clone() {
+                    Some(selected) => selected,
+                    None => return,
+                };
+                {
+                    let mut guard = match view.conversations.lock() {
+                        Ok(guard) => guard,
+                        Err(poisoned) => poisoned.into_inner(),
+                    };
+                    let conversation = guard.iter_mut().find(|conversation| conversation.screen_name == selected);
+                    if let Some(conversation) = conversation {
+                        ::timeline::home::select_row(listboxrow, &mut conversation.messages);
+                    }
+                }
+                view.update_labels();
+            });
+        }
+
+        // event definition, when send button is clicked or reply entry is activated
+        let send = {
+            let view = view.clone();
+
+            Rc::new(move || {
+                let screen_name = match view.selected.borrow().clone() {
+                    Some(screen_name) => screen_name,
+                    None => return,
+                };
+                let text = view.entry_reply.get_text().unwrap_or(String::new());
+                if text.trim().is_empty() {
+                    return;
+                }
+                let client = ::api::client(&config, &token);
+                match send_message(&client, &screen_name, &text) {
+                    Ok(row) => {
+                        errorbar.hide();
+                        view.entry_reply.set_text("");
+                        view.merge(&screen_name, &vec![row]);
+                    },
+                    Err(err) => {
+                        error!("{:?}", err);
+                        errorbar.show_error(&err);
+                    },
+                }
+            })
+        };
+        {
+            let send = send.clone();
+            entry_reply.connect_activate(move |_| send());
+        }
+        button_send.connect_clicked(move |_| send());
+
+        view
+    }
+
+    // render rebuilds the conversation list and selects the previously selected conversation again
+    pub fn render(&self) {
+        let selected = self.selected.borrow().clone();
+        let labels = self.conversation_labels();
+
+        for widget in self.conversation_listbox.get_children() {
+            self.conversation_listbox.remove(&widget);
+        }
+        let mut selected_row = None;
+        for (screen_name, text) in labels {
+            let label = gtk::Label::new(Some(text.as_str()));
+            label.set_padding(16, 6);
+            label.set_xalign(0.0);
+
+            let listboxrow = gtk::ListBoxRow::new();
+            listboxrow.set_name(&screen_name);
+            listboxrow.add(&label);
+            self.conversation_listbox.insert(&listboxrow, -1);
+            if Some(&screen_name) == selected.as_ref() {
+                selected_row = Some(listboxrow);
+            }
+        }
+        self.conversation_listbox.show_all();
+
+        match selected_row {
+            Some(listboxrow) => self.conversation_listbox.select_row(Some(&listboxrow)),
+            None => {
+                *self.selected.borrow_mut() = None;
+                self.render_thread();
+            },
+        }
+        self.update_labels();
+    }
+
+    // merge adds fetched or sent rows to the conversation with screen_name
+    pub fn merge(&self, screen_name: &str, rows: &Vec<TimelineRow>) {
+        let added = {
+            let mut guard = match self.conversations.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            merge(&mut guard, screen_name, rows, self.config.toml.direct_messages.limits.get())
+        };
+        if !added.is_empty() {
+            self.render();
+        }
+    }
+
+    // fetch requests messages newer than the cursor and merges them
+    pub fn fetch(&self, client: &::api::Client) -> Result<(), ::api::ApiError> {
+        let cursor = &self.config.toml.direct_messages;
+        let conversations = try!(direct_messages(
+            client,
+            Some(cursor.last_update_id.get()),
+            cursor.limits.get() as i32,
+        ));
+        if let Some(id) = latest_id(&conversations) {
+            cursor.last_update_id.set(id);
+        }
+        for conversation in conversations {
+            self.merge(&conversation.screen_name, &conversation.messages);
+        }
+        Ok(())
+    }
+
+    fn render_thread(&self) {
+        let selected = self.selected.borrow().clone();
+        self.entry_reply.set_sensitive(selected.is_some());
+        let messages = {
+            let guard = match self.conversations.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            guard
+                .iter()
+                .find(|conversation| Some(&conversation.screen_name) == selected.as_ref())
+                .map(|conversation| conversation.messages.clone())
+                .unwrap_or(Vec::new())
+        };
+        match ::timeline::home::update_home(&self.listbox, &messages, false, false) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+    }
+
+    // update_labels updates unread counts of the conversation list and the side pane
+    fn update_labels(&self) {
+        let labels: BTreeMap<String, String> = self.conversation_labels().into_iter().collect();
+        for widget in self.conversation_listbox.get_children() {
+            let listboxrow = match widget.downcast::<gtk::ListBoxRow>() {
+                Ok(listboxrow) => listboxrow,
+                Err(_) => continue,
+            };
+            let text = match listboxrow.get_name().and_then(|name| labels.get(&name).cloned()) {
+                Some(text) => text,
+                None => continue,
+            };
+            if let Some(label) = listboxrow.get_child().and_then(|child| child.downcast::<gtk::Label>().ok()) {
+                label.set_text(&text);
+            }
+        }
+
+        let guard = match self.conversations.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        self.label.set_text(&unread_label("Direct Messages", &guard));
+    }
+
+    // conversation_labels returns screen names and labels with unread counts, the lock is released on return
+    // because selecting a row emits signals which lock conversations again
+    fn conversation_labels(&self) -> Vec<(String, String)> {
+        let guard = match self.conversations.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        guard
+            .iter()
+            .map(|conversation| {
+                let name = format!("@{}", conversation.screen_name);
+                (conversation.screen_name.clone(), ::timeline::utils::unread_label(&name, &conversation.messages))
+            })
+            .collect()
+    }
+}
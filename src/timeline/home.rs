@@ -1,15 +1,22 @@
+extern crate chrono;
 extern crate curl;
 extern crate crypto;
-extern crate egg_mode;
 extern crate rustc_serialize;
 extern crate time;
 
+use chrono::{DateTime, Local};
 use gtk;
 use gtk::{Image, Label};
 use gtk::{Orientation, RevealerTransitionType};
 use gtk::prelude::*;
 use regex;
+use rustc_serialize::json;
+use std::cell::Cell;
 use std::clone::Clone;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::Mutex;
+use timeline::utils::Action;
 
 // TimelineError
 #[derive(Debug)]
@@ -67,6 +74,8 @@ impl From<::utils::UtilsError> for CreateWidgetError {
 pub struct TimelineRow {
     pub tweet: Tweet,
     pub unread: bool,
+    // gap is since_id of tweets missing below this row, they are loaded by backfill
+    pub gap: Option<u64>,
 }
 
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
@@ -75,21 +84,42 @@ pub struct Tweet {
     pub id: u64,
     pub text: String,
     pub attr: String,
-    pub user: User, // pub retweeted_status: RetweetedStatus,
+    pub user: User,
+    pub in_reply_to_status_id: Option<u64>,
+    pub quoted_status: Option<Box<Tweet>>,
+    // retweeted_status is the original tweet of a retweet, user is the retweeter then
+    pub retweeted_status: Option<RetweetedStatus>,
+    // engagement is of the original tweet, it is None for direct messages
+    pub engagement: Option<Engagement>,
+}
+
+// Engagement is the counts of the tweet and whether the authenticated user retweeted and liked it
+#[derive(Clone, Debug, Default, RustcEncodable, RustcDecodable)]
+pub struct Engagement {
+    pub retweet_count: u64,
+    pub favorite_count: u64,
+    pub retweeted: bool,
+    pub favorited: bool,
 }
 
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
 pub struct User {
     pub screen_name: String,
     pub profile_image_url: String,
+    // id is None in caches written before it is kept
+    pub id: Option<u64>,
+    // following is whether the authenticated user follows this user, None when it is unknown
+    pub following: Option<bool>,
 }
 
 #[derive(Clone, Debug, RustcEncodable, RustcDecodable)]
 pub struct RetweetedStatus {
-    pub id: i64,
+    pub id: u64,
     pub text: String,
     pub user: User,
     pub created_at: String,
+    // in_reply_to_status_id is None also in caches written before it is kept
+    pub in_reply_to_status_id: Option<u64>,
 }
 
 pub fn fixup_home(timeline: &mut Vec<TimelineRow>, limit: usize) {
@@ -110,42 +140,281 @@ pub fn update_home(
             listbox.remove(&widget);
         }
     }
-    // いっぱいつぶやくユーザをフィルタ
-    let muted_user = Some(None);
     let mut index: i32 = 0;
 
     for status in timeline {
         if unread_filter == true && status.unread == false {
             continue;
         }
-        for muted in muted_user.clone().unwrap() {
-            if status.tweet.user.screen_name == muted {
-                break;
-            } else if muted == muted_user.clone().unwrap().last().unwrap().to_string() {
-                let listboxrow = gtk::ListBoxRow::new();
-                let revealer = try!(create_revealer(status.clone()));
-                listboxrow.add(&revealer);
-                listbox.insert(&listboxrow, index);
-
-                index += 1;
-                try!(show_listboxrow(&listboxrow));
-            }
+        // tweets of muted users are not shown, the gap below them is kept
+        if !::timeline::utils::is_muted(&status.tweet) {
+            listbox.insert(&try!(create_row(status)), index);
+            index += 1;
+        }
+        if status.gap.is_some() {
+            listbox.insert(&create_gap_row(status), index);
+            index += 1;
         }
     }
     return Ok(());
 }
 
+// append_home appends rows of older tweets to the bottom of listbox
+pub fn append_home(listbox: &gtk::ListBox, timeline: &Vec<TimelineRow>) -> Result<(), TimelineError> {
+    for status in timeline {
+        if !::timeline::utils::is_muted(&status.tweet) {
+            listbox.insert(&try!(create_row(status)), -1);
+        }
+        if status.gap.is_some() {
+            listbox.insert(&create_gap_row(status), -1);
+        }
+    }
+    Ok(())
+}
+
+// create_row creates the row of the tweet, it is collapsed to the reason when it matches a mute rule
+pub fn create_row(row: &TimelineRow) -> Result<gtk::ListBoxRow, TimelineError> {
+    if let Some(reason) = ::timeline::utils::mute_reason(&row.tweet) {
+        return Ok(create_muted_row(row, &reason));
+    }
+    let listboxrow = gtk::ListBoxRow::new();
+    let revealer = try!(create_revealer(row.clone()));
+    listboxrow.add(&revealer);
+    connect_context_menu(&listboxrow, &row.tweet);
+    try!(show_listboxrow(&listboxrow));
+    Ok(listboxrow)
+}
+
+// create_muted_row creates the stub row of the tweet muted by reason, the tweet is revealed by its link
+pub fn create_muted_row(row: &TimelineRow, reason: &str) -> gtk::ListBoxRow {
+    let label_muted = Label::new(None);
+    label_muted.set_markup(&format!(
+        r#"<small>muted: {} <a href="reveal">Show</a></small>"#,
+        ::timeline::utils::escape_markup(reason)
+    ));
+    label_muted.set_padding(4, 6);
+    label_muted.set_xalign(0.0);
+
+    // event definition, when show link is clicked, the stub is replaced by the revealer of the tweet
+    {
+        let row = row.clone();
+
+        label_muted.connect_activate_link(move |label, _| {
+            let listboxrow = match label.get_parent().and_then(|parent| parent.downcast::<gtk::ListBoxRow>().ok()) {
+                Some(listboxrow) => listboxrow,
+                None => return Inhibit(true),
+            };
+            match create_revealer(row.clone()) {
+                Ok(revealer) => {
+                    listboxrow.remove(label);
+                    listboxrow.add(&revealer);
+                    listboxrow.set_selectable(true);
+                    listboxrow.set_activatable(true);
+                    connect_context_menu(&listboxrow, &row.tweet);
+                    if let Err(err) = show_listboxrow(&listboxrow) {
+                        error!("{:?}", err);
+                    }
+                },
+                Err(err) => error!("{:?}", err),
+            }
+            Inhibit(true)
+        });
+    }
+
+    let listboxrow = gtk::ListBoxRow::new();
+    listboxrow.add(&label_muted);
+    listboxrow.set_selectable(false);
+    listboxrow.set_activatable(false);
+    listboxrow.show_all();
+    listboxrow
+}
+
+// connect_context_menu pops up the menu of the row by the secondary button, it mutes the poster of tweet
+// and the author of the retweeted one
+pub fn connect_context_menu(listboxrow: &gtk::ListBoxRow, tweet: &Tweet) {
+    let mut users = vec![tweet.user.clone()];
+    if tweet.retweeted_status.is_some() {
+        users.push(tweet.author().clone());
+    }
+
+    listboxrow.connect_button_press_event(move |_, event| {
+        if event.get_button() != 3 {
+            return Inhibit(false);
+        }
+        let menu = gtk::Menu::new();
+        for user in &users {
+            let item = gtk::MenuItem::new_with_label(&format!("Mute @{}", user.screen_name));
+            let user = user.clone();
+            item.connect_activate(move |_| ::timeline::utils::run_mute(&user));
+            menu.append(&item);
+        }
+        menu.show_all();
+        menu.popup_easy(event.get_button(), event.get_time());
+        Inhibit(true)
+    });
+}
+
+// create_gap_row creates the row to load tweets missing below row, its link is handled by the gap link handler
+pub fn create_gap_row(row: &TimelineRow) -> gtk::ListBoxRow {
+    let label_gap = Label::new(None);
+    label_gap.set_markup(&format!(r#"<a href="gap:{}">Load missing tweets</a>"#, row.tweet.id));
+    label_gap.set_padding(4, 6);
+    ::timeline::utils::connect_links(&label_gap);
+
+    let listboxrow = gtk::ListBoxRow::new();
+    listboxrow.add(&label_gap);
+    listboxrow.set_selectable(false);
+    listboxrow.set_activatable(false);
+    listboxrow.show_all();
+    listboxrow
+}
+
+// create_quoted_card creates the bordered card of the quoted tweet, its timestamp opens the thread of it
+pub fn create_quoted_card(tweet: &Tweet) -> Result<gtk::Frame, CreateWidgetError> {
+    let profile_image_filename = try!(::utils::get_profile_image(&tweet.user.profile_image_url));
+    let image_profile_image = Image::new_from_file(profile_image_filename);
+    image_profile_image.set_padding(4, 4);
+    image_profile_image.set_valign(gtk::Align::Start);
+
+    let user_label = Label::new(None);
+    user_label.set_markup(&::timeline::utils::format_user_links(&format!("@{}", tweet.user.screen_name)));
+    user_label.set_xalign(0.0);
+    ::timeline::utils::connect_links(&user_label);
+
+    let created_at_label = Label::new(None);
+    created_at_label.set_markup(&format!(r#"<a href="thread:{}">{}</a>"#, tweet.id, tweet.created_at));
+    ::timeline::utils::connect_links(&created_at_label);
+
+    let box_header = gtk::Box::new(Orientation::Horizontal, 2);
+    box_header.pack_start(&user_label, true, true, 0);
+    box_header.pack_start(&created_at_label, false, false, 0);
+
+    let body = try!(::timeline::utils::format_tweet_body(&tweet.text));
+    let label_body = Label::new(None);
+    label_body.set_text(body.as_ref());
+    label_body.set_selectable(true);
+    label_body.set_use_markup(true);
+    label_body.set_line_wrap(true);
+    label_body.set_xalign(0.0);
+    ::timeline::utils::connect_links(&label_body);
+
+    let box_label = gtk::Box::new(Orientation::Vertical, 2);
+    box_label.pack_start(&box_header, false, false, 0);
+    box_label.pack_start(&label_body, true, true, 0);
+
+    let box_card = gtk::Box::new(Orientation::Horizontal, 2);
+    box_card.set_border_width(4);
+    box_card.pack_start(&image_profile_image, false, false, 0);
+    box_card.pack_start(&box_label, true, true, 0);
+
+    let frame = gtk::Frame::new(None);
+    frame.set_shadow_type(gtk::ShadowType::EtchedIn);
+    frame.add(&box_card);
+    Ok(frame)
+}
+
+// create_retweeted_by_label creates the line of the retweeter under the original tweet
+fn create_retweeted_by_label(tweet: &Tweet) -> Result<Label, CreateWidgetError> {
+    let label_retweeted_by = Label::new(None);
+    label_retweeted_by.set_markup(&format!(
+        "<small>retweeted by {} at {}</small>",
+        ::timeline::utils::format_user_links(&format!("@{}", tweet.user.screen_name)),
+        tweet.created_at
+    ));
+    label_retweeted_by.set_xalign(0.0);
+    ::timeline::utils::connect_links(&label_retweeted_by);
+    Ok(label_retweeted_by)
+}
+
+// create_action_bar creates buttons of reply, retweet, like and quote on the expanded row,
+// they are run by the action handler and retweet and like buttons are updated by its result
+fn create_action_bar(tweet: &Tweet, engagement: &Engagement) -> gtk::Box {
+    let button_reply = gtk::Button::new_with_label("Reply");
+    let button_retweet = gtk::ToggleButton::new();
+    let button_like = gtk::ToggleButton::new();
+    let button_quote = gtk::Button::new_with_label("Quote");
+    // updating is set while buttons are updated, toggled signals are not actions then
+    let updating = Rc::new(Cell::new(true));
+    set_engagement(&button_retweet, &button_like, engagement);
+    updating.set(false);
+
+    {
+        let tweet = tweet.clone();
+
+        button_reply.connect_clicked(move |_| {
+            ::timeline::utils::run_action(Action::Reply, &tweet);
+        });
+    }
+    {
+        let tweet = tweet.clone();
+
+        button_quote.connect_clicked(move |_| {
+            ::timeline::utils::run_action(Action::Quote, &tweet);
+        });
+    }
+    for button in &[button_retweet.clone(), button_like.clone()] {
+        let tweet = tweet.clone();
+        let button_retweet = button_retweet.clone();
+        let button_like = button_like.clone();
+        let updating = updating.clone();
+
+        button.connect_toggled(move |button| {
+            if updating.get() {
+                return;
+            }
+            let action = match (*button == button_retweet, button.get_active()) {
+                (true, true) => Action::Retweet,
+                (true, false) => Action::Unretweet,
+                (false, true) => Action::Like,
+                (false, false) => Action::Unlike,
+            };
+            let result = ::timeline::utils::run_action(action, &tweet);
+            updating.set(true);
+            match result {
+                Some(engagement) => set_engagement(&button_retweet, &button_like, &engagement),
+                // the button is restored when the action failed
+                None => button.set_active(!button.get_active()),
+            }
+            updating.set(false);
+        });
+    }
+
+    let box_actions = gtk::Box::new(Orientation::Horizontal, 2);
+    box_actions.pack_start(&button_reply, false, false, 0);
+    box_actions.pack_start(&button_retweet, false, false, 0);
+    box_actions.pack_start(&button_like, false, false, 0);
+    box_actions.pack_start(&button_quote, false, false, 0);
+    if ::timeline::utils::is_own(tweet) {
+        let button_delete = gtk::Button::new_with_label("Delete");
+        let tweet = tweet.clone();
+
+        button_delete.connect_clicked(move |_| {
+            ::timeline::utils::run_action(Action::Delete, &tweet);
+        });
+        box_actions.pack_end(&button_delete, false, false, 0);
+    }
+    box_actions
+}
+
+fn set_engagement(button_retweet: &gtk::ToggleButton, button_like: &gtk::ToggleButton, engagement: &Engagement) {
+    button_retweet.set_active(engagement.retweeted);
+    button_retweet.set_label(&format!("Retweet {}", engagement.retweet_count));
+    button_like.set_active(engagement.favorited);
+    button_like.set_label(&format!("Like {}", engagement.favorite_count));
+}
+
 pub fn create_revealer(row: TimelineRow) -> Result<gtk::Revealer, CreateWidgetError> {
     let create_box_header = move |tweet: Tweet| -> Result<gtk::Box, CreateWidgetError> {
         let user_label = Label::new(None);
-        let user = format!("<b>{}</b>", tweet.attr);
+        let user = format!("<b>{}</b>", ::timeline::utils::format_user_links(&tweet.attr));
         user_label.set_text(user.as_ref());
         user_label.set_selectable(true);
         user_label.set_use_markup(true);
         user_label.set_xalign(0.0);
+        ::timeline::utils::connect_links(&user_label);
 
         let created_at_label = Label::new(None);
-        created_at_label.set_text(tweet.created_at.as_ref());
+        created_at_label.set_text(tweet.posted_at());
         let box_header = gtk::Box::new(Orientation::Horizontal, 2);
         box_header.pack_start(&user_label, true, true, 0);
         box_header.pack_start(&created_at_label, false, false, 0);
@@ -163,18 +432,25 @@ pub fn create_revealer(row: TimelineRow) -> Result<gtk::Revealer, CreateWidgetEr
         label_body.set_use_markup(true);
         label_body.set_line_wrap(true);
         label_body.set_xalign(0.0);
+        ::timeline::utils::connect_links(&label_body);
 
         let box_label = gtk::Box::new(Orientation::Vertical, 2);
         box_label.pack_start(&box_header, false, false, 0);
         box_label.pack_start(&label_body, true, true, 0);
+        if tweet.retweeted_status.is_some() {
+            box_label.pack_start(&try!(create_retweeted_by_label(&tweet)), false, false, 0);
+        }
+        if let Some(ref quoted_status) = tweet.quoted_status {
+            box_label.pack_start(&try!(create_quoted_card(quoted_status)), false, false, 4);
+        }
 
         return Ok(box_label);
     };
 
     let create_box_revealer = move |row: TimelineRow| -> Result<gtk::Box, CreateWidgetError> {
-        println!("{}", &row.tweet.user.profile_image_url);
+        println!("{}", &row.tweet.author().profile_image_url);
         let profile_image_filename = try!(::utils::get_profile_image(
-            &row.tweet.user.profile_image_url,
+            &row.tweet.author().profile_image_url,
         ));
         let image_profile_image = Image::new_from_file(profile_image_filename);
         image_profile_image.set_padding(4, 4);
@@ -197,7 +473,7 @@ pub fn create_revealer(row: TimelineRow) -> Result<gtk::Revealer, CreateWidgetEr
         label_id.set_visible(false);
 
         let label_profile_image = Label::new(None);
-        let profile_image_url = format!("{}", row.tweet.user.profile_image_url);
+        let profile_image_url = format!("{}", row.tweet.author().profile_image_url);
         label_profile_image.set_text(profile_image_url.as_ref());
         label_profile_image.set_visible(false);
 
@@ -237,14 +513,15 @@ pub fn create_revealer(row: TimelineRow) -> Result<gtk::Revealer, CreateWidgetEr
 pub fn create_expanded_revealer(row: TimelineRow) -> Result<gtk::Revealer, CreateWidgetError> {
     let create_expanded_box_header = move |tweet: Tweet| -> Result<gtk::Box, CreateWidgetError> {
         let user_label = Label::new(None);
-        let user = format!("{}", tweet.attr);
+        let user = format!("{}", ::timeline::utils::format_user_links(&tweet.attr));
         user_label.set_text(user.as_ref());
         user_label.set_selectable(true);
         user_label.set_use_markup(true);
         user_label.set_xalign(0.0);
+        ::timeline::utils::connect_links(&user_label);
 
         let created_at_label = Label::new(None);
-        created_at_label.set_text(tweet.created_at.as_ref());
+        created_at_label.set_text(tweet.posted_at());
 
         let box_header = gtk::Box::new(Orientation::Horizontal, 2);
         box_header.pack_start(&user_label, true, true, 0);
@@ -263,17 +540,34 @@ pub fn create_expanded_revealer(row: TimelineRow) -> Result<gtk::Revealer, Creat
         label_body.set_use_markup(true);
         label_body.set_line_wrap(true);
         label_body.set_xalign(0.0);
+        ::timeline::utils::connect_links(&label_body);
+
+        // the link is handled by the thread link handler
+        let label_thread = Label::new(None);
+        label_thread.set_markup(&format!(r#"<a href="thread:{}">Show conversation</a>"#, tweet.status_id()));
+        label_thread.set_xalign(0.0);
+        ::timeline::utils::connect_links(&label_thread);
 
         let box_label = gtk::Box::new(Orientation::Vertical, 2);
         box_label.pack_start(&box_header, false, false, 0);
         box_label.pack_start(&label_body, true, true, 0);
+        if tweet.retweeted_status.is_some() {
+            box_label.pack_start(&try!(create_retweeted_by_label(&tweet)), false, false, 0);
+        }
+        if let Some(ref quoted_status) = tweet.quoted_status {
+            box_label.pack_start(&try!(create_quoted_card(quoted_status)), false, false, 4);
+        }
+        box_label.pack_start(&label_thread, false, false, 0);
+        if let Some(ref engagement) = tweet.engagement {
+            box_label.pack_start(&create_action_bar(&tweet, engagement), false, false, 0);
+        }
 
         return Ok(box_label);
     };
 
     let create_expanded_box_revealer = move |row: TimelineRow| -> Result<gtk::Box, CreateWidgetError> {
         let profile_image_filename = try!(::utils::get_profile_image(
-            &row.tweet.user.profile_image_url,
+            &row.tweet.author().profile_image_url,
         ));
         let image_profile_image = Image::new_from_file(profile_image_filename);
         image_profile_image.set_padding(4, 4);
@@ -296,7 +590,7 @@ pub fn create_expanded_revealer(row: TimelineRow) -> Result<gtk::Revealer, Creat
         label_id.set_visible(false);
 
         let label_profile_image = Label::new(None);
-        let profile_image_url = format!("{}", row.tweet.user.profile_image_url);
+        let profile_image_url = format!("{}", row.tweet.author().profile_image_url);
         label_profile_image.set_text(profile_image_url.as_ref());
         label_profile_image.set_visible(false);
 
@@ -326,14 +620,118 @@ pub fn create_expanded_revealer(row: TimelineRow) -> Result<gtk::Revealer, Creat
 
     let revealer = try!(create_expanded_revealer(row.clone()));
 
-    // ToDo: display when event_box clicked
-    // let popover = Popover::new(Some(&event_box));
-    // let popover_button = gtk::Button::new_with_label("hogehoge");
-    // popover.add(&popover_button);
-
     Ok(revealer)
 }
 
+// insert_posted inserts the tweet posted from compose at the top of home before it is fetched
+pub fn insert_posted(listbox: &gtk::ListBox, home: &Mutex<Vec<TimelineRow>>, row: TimelineRow, limit: usize) {
+    let rows = vec![row];
+    {
+        let mut guard = match home.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        ::timeline::utils::prepend(&mut guard, &rows, limit);
+    }
+    match update_home(listbox, &rows, true, false) {
+        Ok(_) => (),
+        Err(err) => error!("{:?}", err),
+    }
+}
+
+// select_row clears unread flag of the selected row and expands it, and returns the id of the row
+pub fn select_row(listboxrow: &Option<gtk::ListBoxRow>, timeline: &mut Vec<TimelineRow>) -> Option<u64> {
+    let revealer = match listboxrow.clone() {
+        Some(listboxrow) => {
+            match listboxrow.get_child() {
+                Some(widget) => {
+                    match widget.downcast::<gtk::Revealer>() {
+                        Ok(revealer) => revealer,
+                        Err(err) => {
+                            error!("{:?}", err);
+                            return None;
+                        },
+                    }
+                },
+                None => return None,
+            }
+        },
+        None => return None,
+    };
+    let box_revealer = match revealer.get_child() {
+        Some(widget) => {
+            match widget.downcast::<gtk::Box>() {
+                Ok(box_revealer) => box_revealer,
+                Err(err) => {
+                    error!("{:?}", err);
+                    return None;
+                },
+            }
+        },
+        None => return None,
+    };
+    let unread_image = match box_revealer.get_children()[2].clone().downcast::<Image>() {
+        Ok(unread_image) => unread_image,
+        Err(err) => {
+            error!("{:?}", err);
+            return None;
+        },
+    };
+    let id_label = match box_revealer.get_children()[4].clone().downcast::<Label>() {
+        Ok(id_label) => id_label,
+        Err(err) => {
+            error!("{:?}", err);
+            return None;
+        },
+    };
+
+    // clear unread image to empty
+    unread_image.clear();
+    unread_image.set_padding(::vars::UNREAD_IMAGE_SIZE, ::vars::UNREAD_IMAGE_SIZE);
+
+    let id: u64 = match id_label.get_text() {
+        Some(id_str) => {
+            match u64::from_str_radix(&id_str, 10) {
+                Ok(id) => id,
+                Err(err) => {
+                    error!("{}", err);
+                    return None;
+                },
+            }
+        },
+        None => return None,
+    };
+    // update timeline unread flag to clear
+    let listboxrow = match listboxrow.clone() {
+        Some(listboxrow) => listboxrow,
+        None => return None,
+    };
+    for row in timeline {
+        if row.tweet.id == id {
+            row.unread = false;
+            // expand selected listboxrow
+            listboxrow.remove(&revealer);
+            let revealer = match create_expanded_revealer(row.clone()) {
+                Ok(revealer) => revealer,
+                Err(err) => {
+                    error!("{:?}", err);
+                    return None;
+                },
+            };
+            listboxrow.add(&revealer);
+            match show_listboxrow(&listboxrow) {
+                Ok(_) => (),
+                Err(err) => {
+                    error!("{:?}", err);
+                    return None;
+                },
+            }
+            break;
+        }
+    }
+    Some(id)
+}
+
 pub fn show_listboxrow(listboxrow: &gtk::ListBoxRow) -> Result<(), gtk::Widget> {
     listboxrow.show_all();
     let revealer = listboxrow
@@ -352,47 +750,280 @@ pub fn show_listboxrow(listboxrow: &gtk::ListBoxRow) -> Result<(), gtk::Widget>
 }
 
 pub fn home_timeline(
-    token: &egg_mode::Token,
+    client: &::api::Client,
     since_id: Option<u64>,
+    max_id: Option<u64>,
     count: i32,
-) -> Result<Vec<TimelineRow>, egg_mode::error::Error> {
-    let mut timeline: Vec<TimelineRow> = Vec::new();
-    let home_timeline = egg_mode::tweet::home_timeline(&token).with_page_size(count);
-    for status in &home_timeline.call(since_id, None).unwrap().response {
-        let mut text = status.text.clone();
-        let mut attr = format!("@{}", status.clone().user.unwrap().screen_name);
-        if let Some(ref screen_name) = status.in_reply_to_screen_name {
-            attr = format!(
-                "@{} --> in reply to @{}",
-                status.clone().user.unwrap().screen_name,
-                screen_name
-            );
+) -> Result<Vec<TimelineRow>, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("count".to_owned(), format!("{}", count));
+    params.insert("tweet_mode".to_owned(), "extended".to_owned());
+    if let Some(since_id) = since_id {
+        params.insert("since_id".to_owned(), format!("{}", since_id));
+    }
+    if let Some(max_id) = max_id {
+        params.insert("max_id".to_owned(), format!("{}", max_id));
+    }
+    ::timeline::utils::statuses(client, "statuses/home_timeline.json", &params)
+}
+
+// mark_gap marks the oldest row of a full page fetched after since_id,
+// because tweets older than the page and newer than since_id may be missing
+pub fn mark_gap(rows: &mut Vec<TimelineRow>, since_id: u64, count: i32) {
+    // since_id is 1 at first launch, and there is nothing to fill
+    if since_id <= 1 || rows.len() < count as usize {
+        return;
+    }
+    if let Some(oldest) = rows.last_mut() {
+        oldest.gap = Some(since_id);
+    }
+}
+
+// merge_page inserts rows of a page newer than timeline at the top of it and returns the inserted rows.
+// rows already in timeline like tweets posted from compose are skipped, and the gap marked on the oldest row
+// of the page by mark_gap moves to the oldest inserted row, otherwise it is lost with the skipped row
+pub fn merge_page(timeline: &mut Vec<TimelineRow>, mut rows: Vec<TimelineRow>, limit: usize) -> Vec<TimelineRow> {
+    let gap = rows.last().and_then(|row| row.gap);
+    rows.retain(|row| !timeline.iter().any(|known| known.tweet.id == row.tweet.id));
+    if let Some(oldest) = rows.last_mut() {
+        oldest.gap = oldest.gap.or(gap);
+    }
+    for (index, row) in rows.iter().enumerate() {
+        timeline.insert(index, row.clone());
+    }
+    fixup_home(timeline, limit);
+    rows
+}
+
+// backfill requests tweets missing below the row of id with max_id paging and inserts them after the row,
+// the gap moves down to the oldest inserted row when more than limit tweets are missing
+pub fn backfill(
+    client: &::api::Client,
+    timeline: &mut Vec<TimelineRow>,
+    id: u64,
+    count: i32,
+    limit: usize,
+) -> Result<(), ::api::ApiError> {
+    let mut bottom = match timeline.iter().position(|row| row.tweet.id == id) {
+        Some(index) => index,
+        None => return Ok(()),
+    };
+    let since_id = match timeline[bottom].gap.take() {
+        Some(since_id) => since_id,
+        None => return Ok(()),
+    };
+
+    let mut inserted = 0;
+    loop {
+        // since_id is not requested, the page has to include it or a known row to tell the gap is closed
+        let max_id = timeline[bottom].tweet.id - 1;
+        let rows = match home_timeline(client, None, Some(max_id), count) {
+            Ok(rows) => rows,
+            Err(err) => {
+                timeline[bottom].gap = Some(since_id);
+                return Err(err);
+            },
+        };
+        let (next, closed) = fill_gap(timeline, bottom, rows, since_id);
+        inserted += next - bottom;
+        bottom = next;
+        if closed {
+            break;
         }
-        if let Some(ref retweeted_status) = status.retweeted_status {
-            text = retweeted_status.text.clone();
-            attr = format!(
-                "@{} retweeted from @{}",
-                status.clone().user.unwrap().screen_name,
-                retweeted_status.clone().user.unwrap().screen_name
-            );
+        if inserted >= limit {
+            timeline[bottom].gap = Some(since_id);
+            break;
         }
-        let created_at_local = status.created_at.naive_local();
-        timeline.push(TimelineRow {
-            tweet: Tweet {
-                created_at: format!("{}", created_at_local),
-                id: status.id.clone(),
-                text: text,
-                attr: attr,
-                user: User {
-                    screen_name: status.clone().user.unwrap().screen_name.clone(),
-                    profile_image_url: status.clone().user.unwrap().profile_image_url.clone(),
-                },
-            },
+    }
+    Ok(())
+}
+
+// fill_gap inserts rows of a backfill page newer than since_id after the row at bottom, and returns the index
+// of the oldest inserted row with whether the gap is closed. It is closed when the page reaches since_id or a
+// known row, or when no older tweet is left, a short page is not enough as the API returns them in the middle
+fn fill_gap(
+    timeline: &mut Vec<TimelineRow>,
+    mut bottom: usize,
+    rows: Vec<TimelineRow>,
+    since_id: u64,
+) -> (usize, bool) {
+    let mut closed = rows.is_empty();
+    for row in rows {
+        if row.tweet.id <= since_id || timeline.iter().any(|known| known.tweet.id == row.tweet.id) {
+            closed = true;
+            continue;
+        }
+        bottom += 1;
+        timeline.insert(bottom, row);
+    }
+    (bottom, closed)
+}
+
+impl TimelineRow {
+    // from_json converts a status object of twitter API into an unread row
+    pub fn from_json(status: &json::Json) -> Result<TimelineRow, ::api::ApiError> {
+        Ok(TimelineRow {
+            tweet: try!(Tweet::from_json(status)),
             unread: true,
-        });
-        debug!("{:?}", status);
+            gap: None,
+        })
+    }
+}
+
+impl Tweet {
+    pub fn from_json(status: &json::Json) -> Result<Tweet, ::api::ApiError> {
+        let user = try!(User::from_json(try!(field(status, "user"))));
+        let mut text = try!(status_text(status));
+        let mut attr = format!("@{}", user.screen_name);
+        if let Some(screen_name) = status.find("in_reply_to_screen_name").and_then(|v| v.as_string()) {
+            attr = format!("@{} --> in reply to @{}", user.screen_name, screen_name);
+        }
+        // text and attr of a retweet are the ones of the original tweet, the quoted tweet is in it too
+        let mut quoted_status = status.find("quoted_status");
+        let mut retweeted = None;
+        let mut engagement = Engagement::from_json(status);
+        if let Some(retweeted_status) = status.find("retweeted_status").and_then(|v| v.as_object()) {
+            let retweeted_status = json::Json::Object(retweeted_status.clone());
+            let retweeted_user = try!(User::from_json(try!(field(&retweeted_status, "user"))));
+            text = try!(status_text(&retweeted_status));
+            attr = format!("@{}", retweeted_user.screen_name);
+            if let Some(screen_name) = retweeted_status.find("in_reply_to_screen_name").and_then(|v| v.as_string()) {
+                attr = format!("@{} --> in reply to @{}", retweeted_user.screen_name, screen_name);
+            }
+            quoted_status = status.find_path(&["retweeted_status", "quoted_status"]);
+            engagement = Engagement::from_json(&retweeted_status);
+            retweeted = Some(RetweetedStatus {
+                id: try!(field(&retweeted_status, "id").and_then(|v| {
+                    v.as_u64().ok_or("id is not a number".to_owned())
+                })),
+                text: text.clone(),
+                user: retweeted_user,
+                created_at: try!(created_at_local(&try!(string_field(&retweeted_status, "created_at")))),
+                in_reply_to_status_id: retweeted_status.find("in_reply_to_status_id").and_then(|v| v.as_u64()),
+            });
+        }
+        let quoted_status = match quoted_status {
+            Some(quoted_status) if quoted_status.is_object() => Some(Box::new(try!(Tweet::from_json(quoted_status)))),
+            _ => None,
+        };
+        let created_at = try!(created_at_local(&try!(string_field(status, "created_at"))));
+        Ok(Tweet {
+            created_at: created_at,
+            id: try!(field(status, "id").and_then(|v| v.as_u64().ok_or("id is not a number".to_owned()))),
+            text: text,
+            attr: attr,
+            user: user,
+            in_reply_to_status_id: status.find("in_reply_to_status_id").and_then(|v| v.as_u64()),
+            quoted_status: quoted_status,
+            retweeted_status: retweeted,
+            engagement: Some(engagement),
+        })
+    }
+
+    // status_id returns the id of the original tweet for a retweet, actions like reply and like target it
+    pub fn status_id(&self) -> u64 {
+        self.retweeted_status.as_ref().map(|status| status.id).unwrap_or(self.id)
+    }
+
+    // author returns the user who posted the original tweet
+    pub fn author(&self) -> &User {
+        self.retweeted_status.as_ref().map(|status| &status.user).unwrap_or(&self.user)
+    }
+
+    // full_text returns the untruncated text of the original tweet, not the "RT @user:" text of a retweet
+    pub fn full_text(&self) -> &str {
+        self.retweeted_status.as_ref().map(|status| status.text.as_str()).unwrap_or(self.text.as_str())
+    }
+
+    // in_reply_to returns in_reply_to_status_id of the original tweet, a retweet itself replies to nothing
+    pub fn in_reply_to(&self) -> Option<u64> {
+        match self.retweeted_status {
+            Some(ref status) => status.in_reply_to_status_id,
+            None => self.in_reply_to_status_id,
+        }
+    }
+
+    // posted_at returns created_at of the original tweet
+    pub fn posted_at(&self) -> &str {
+        self.retweeted_status.as_ref().map(|status| status.created_at.as_str()).unwrap_or(self.created_at.as_str())
+    }
+}
+
+impl Engagement {
+    pub fn from_json(status: &json::Json) -> Engagement {
+        let count = |key: &str| status.find(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        let flag = |key: &str| status.find(key).and_then(|v| v.as_boolean()).unwrap_or(false);
+        Engagement {
+            retweet_count: count("retweet_count"),
+            favorite_count: count("favorite_count"),
+            retweeted: flag("retweeted"),
+            favorited: flag("favorited"),
+        }
+    }
+}
+
+impl User {
+    pub fn from_json(user: &json::Json) -> Result<User, ::api::ApiError> {
+        Ok(User {
+            screen_name: try!(string_field(user, "screen_name")),
+            profile_image_url: try!(string_field(user, "profile_image_url")),
+            id: user.find("id").and_then(|v| v.as_u64()),
+            following: user.find("following").and_then(|v| v.as_boolean()),
+        })
+    }
+}
+
+pub fn field<'a>(object: &'a json::Json, key: &str) -> Result<&'a json::Json, String> {
+    object.find(key).ok_or(format!("{} is not found", key))
+}
+
+pub fn string_field(object: &json::Json, key: &str) -> Result<String, String> {
+    let value = try!(field(object, key));
+    value.as_string().map(|s| s.to_owned()).ok_or(format!("{} is not a string", key))
+}
+
+// status_text returns full_text of extended tweet mode or text, within display_text_range
+// which excludes leading reply mentions and trailing media links
+pub fn status_text(status: &json::Json) -> Result<String, String> {
+    let text = try!(string_field(status, "full_text").or_else(|_| string_field(status, "text")));
+    let range = status.find("display_text_range").and_then(|v| v.as_array()).and_then(|range| {
+        match (range.get(0).and_then(|v| v.as_u64()), range.get(1).and_then(|v| v.as_u64())) {
+            (Some(start), Some(end)) => Some((start as usize, end as usize)),
+            _ => None,
+        }
+    });
+    match range {
+        Some((start, end)) => Ok(display_range(&text, start, end)),
+        None => Ok(text),
+    }
+}
+
+// display_range returns text from start to end code points,
+// the range counts code points of unescaped text while &amp; &lt; &gt; are escaped in text
+fn display_range(text: &str, start: usize, end: usize) -> String {
+    let mut range = String::new();
+    let mut index = 0;
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match ["&amp;", "&lt;", "&gt;"].iter().find(|entity| rest.starts_with(*entity)) {
+            Some(entity) => entity.len(),
+            None => c.len_utf8(),
+        };
+        if index >= start && index < end {
+            range.push_str(&rest[..len]);
+        }
+        index += 1;
+        rest = &rest[len..];
+    }
+    range
+}
+
+// created_at_local converts created_at of twitter API into local time string
+pub fn created_at_local(created_at: &str) -> Result<String, String> {
+    match DateTime::parse_from_str(created_at, "%a %b %d %H:%M:%S %z %Y") {
+        Ok(created_at) => Ok(format!("{}", created_at.with_timezone(&Local).naive_local())),
+        Err(err) => Err(format!("{:?}", err)),
     }
-    Ok(timeline)
 }
 
 // pub fn print_tweet(tweet: &egg_mode::tweet::Tweet) {
@@ -436,3 +1067,137 @@ pub fn home_timeline(
 //         }
 //     }
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: u64) -> TimelineRow {
+        TimelineRow {
+            tweet: Tweet {
+                created_at: String::new(),
+                id: id,
+                text: format!("tweet {}", id),
+                attr: "@user".to_owned(),
+                user: User {
+                    screen_name: "user".to_owned(),
+                    profile_image_url: String::new(),
+                    id: None,
+                    following: None,
+                },
+                in_reply_to_status_id: None,
+                quoted_status: None,
+                retweeted_status: None,
+                engagement: None,
+            },
+            unread: true,
+            gap: None,
+        }
+    }
+
+    fn rows(ids: &[u64]) -> Vec<TimelineRow> {
+        ids.iter().map(|id| row(*id)).collect()
+    }
+
+    fn ids(timeline: &Vec<TimelineRow>) -> Vec<u64> {
+        timeline.iter().map(|row| row.tweet.id).collect()
+    }
+
+    #[test]
+    fn mark_gap_marks_oldest_row_of_full_page() {
+        let mut page = rows(&[30, 20]);
+        mark_gap(&mut page, 10, 2);
+        assert_eq!(page[0].gap, None);
+        assert_eq!(page[1].gap, Some(10));
+    }
+
+    #[test]
+    fn mark_gap_skips_short_page() {
+        let mut page = rows(&[30, 20]);
+        mark_gap(&mut page, 10, 3);
+        assert!(page.iter().all(|row| row.gap.is_none()));
+    }
+
+    #[test]
+    fn mark_gap_skips_first_launch() {
+        let mut page = rows(&[30, 20]);
+        mark_gap(&mut page, 1, 2);
+        assert!(page.iter().all(|row| row.gap.is_none()));
+    }
+
+    #[test]
+    fn fill_gap_closes_when_page_reaches_since_id() {
+        let mut timeline = rows(&[100, 10]);
+        let (bottom, closed) = fill_gap(&mut timeline, 0, rows(&[90, 80, 10]), 10);
+        assert!(closed);
+        assert_eq!(bottom, 2);
+        assert_eq!(ids(&timeline), vec![100, 90, 80, 10]);
+    }
+
+    #[test]
+    fn fill_gap_closes_when_page_reaches_known_row() {
+        let mut timeline = rows(&[100, 50, 40]);
+        let (bottom, closed) = fill_gap(&mut timeline, 0, rows(&[90, 50, 40]), 10);
+        assert!(closed);
+        assert_eq!(bottom, 1);
+        assert_eq!(ids(&timeline), vec![100, 90, 50, 40]);
+    }
+
+    #[test]
+    fn fill_gap_keeps_gap_open_on_short_page() {
+        // a short page in the middle of the gap does not mean that no tweet is left
+        let mut timeline = rows(&[100, 5]);
+        let (bottom, closed) = fill_gap(&mut timeline, 0, rows(&[90, 80]), 10);
+        assert!(!closed);
+        assert_eq!(bottom, 2);
+        assert_eq!(ids(&timeline), vec![100, 90, 80, 5]);
+    }
+
+    #[test]
+    fn fill_gap_closes_on_empty_page() {
+        let mut timeline = rows(&[100]);
+        let (bottom, closed) = fill_gap(&mut timeline, 0, Vec::new(), 10);
+        assert!(closed);
+        assert_eq!(bottom, 0);
+        assert_eq!(ids(&timeline), vec![100]);
+    }
+
+    #[test]
+    fn fixup_home_trims_to_limit() {
+        let mut timeline = rows(&[3, 2, 1]);
+        fixup_home(&mut timeline, 2);
+        assert_eq!(ids(&timeline), vec![3, 2]);
+    }
+
+    #[test]
+    fn merge_page_moves_gap_from_known_row() {
+        // the oldest row of the page was posted from compose and is already in home
+        let mut timeline = rows(&[50, 5]);
+        let mut page = rows(&[70, 60, 50]);
+        mark_gap(&mut page, 10, 3);
+        let inserted = merge_page(&mut timeline, page, 10);
+        assert_eq!(ids(&inserted), vec![70, 60]);
+        assert_eq!(inserted[1].gap, Some(10));
+        assert_eq!(ids(&timeline), vec![70, 60, 50, 5]);
+        assert_eq!(timeline[1].gap, Some(10));
+        assert_eq!(timeline[2].gap, None);
+    }
+
+    #[test]
+    fn merge_page_keeps_gap_of_inserted_row() {
+        let mut timeline = rows(&[5]);
+        let mut page = rows(&[70, 60]);
+        mark_gap(&mut page, 5, 2);
+        let inserted = merge_page(&mut timeline, page, 10);
+        assert_eq!(inserted[1].gap, Some(5));
+        assert_eq!(ids(&timeline), vec![70, 60, 5]);
+    }
+
+    #[test]
+    fn merge_page_trims_to_limit() {
+        let mut timeline = rows(&[50, 40]);
+        let inserted = merge_page(&mut timeline, rows(&[70, 60]), 3);
+        assert_eq!(ids(&inserted), vec![70, 60]);
+        assert_eq!(ids(&timeline), vec![70, 60, 50]);
+    }
+}
@@ -0,0 +1,205 @@
+extern crate egg_mode;
+extern crate rustc_serialize;
+
+use gtk;
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ops::DerefMut;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use timeline::home::TimelineRow;
+
+// likes_timeline requests tweets liked by screen_name, or by the authenticated user when it is None
+pub fn likes_timeline(
+    client: &::api::Client,
+    screen_name: Option<&str>,
+    since_id: Option<u64>,
+    max_id: Option<u64>,
+    count: i32,
+) -> Result<Vec<TimelineRow>, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("count".to_owned(), format!("{}", count));
+    params.insert("tweet_mode".to_owned(), "extended".to_owned());
+    if let Some(screen_name) = screen_name {
+        params.insert("screen_name".to_owned(), screen_name.to_owned());
+    }
+    if let Some(since_id) = since_id {
+        params.insert("since_id".to_owned(), format!("{}", since_id));
+    }
+    if let Some(max_id) = max_id {
+        params.insert("max_id".to_owned(), format!("{}", max_id));
+    }
+    ::timeline::utils::statuses(client, "favorites/list.json", &params)
+}
+
+// LikesView shows likes of the authenticated user, which are cached, or likes of another user
+#[derive(Clone)]
+pub struct LikesView {
+    pub container: gtk::Box,
+    pub listbox: gtk::ListBox,
+    pub timeline: Arc<Mutex<Vec<TimelineRow>>>,
+    entry_user: gtk::Entry,
+    // screen name and likes of another user, None while likes of the authenticated user are shown
+    others: Rc<RefCell<Option<(String, Vec<TimelineRow>)>>>,
+}
+
+impl LikesView {
+    pub fn new(
+        timeline: Vec<TimelineRow>,
+        config: Arc<::config::Config>,
+        token: Arc<Mutex<egg_mode::Token<'static>>>,
+        errorbar: ::errorbar::ErrorBar,
+    ) -> LikesView {
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 0);
+
+        let box_user = gtk::Box::new(gtk::Orientation::Horizontal, 2);
+        box_user.set_border_width(4);
+        let entry_user = gtk::Entry::new();
+        entry_user.set_placeholder_text(Some("screen name, empty for your likes"));
+        let button_show = gtk::Button::new_with_label("Show");
+        box_user.pack_start(&entry_user, true, true, 0);
+        box_user.pack_start(&button_show, false, false, 0);
+
+        let scrolled_window = gtk::ScrolledWindow::new(None, None);
+        let box_scrolled = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        let listbox = gtk::ListBox::new();
+        let button_older = gtk::Button::new_with_label("Load older likes");
+        box_scrolled.pack_start(&listbox, false, false, 0);
+        box_scrolled.pack_start(&button_older, false, false, 4);
+        scrolled_window.add(&box_scrolled);
+
+        container.pack_start(&box_user, false, false, 0);
+        container.pack_start(&scrolled_window, true, true, 0);
+
+        let view = LikesView {
+            container: container,
+            listbox: listbox,
+            timeline: Arc::new(Mutex::new(timeline)),
+            entry_user: entry_user.clone(),
+            others: Rc::new(RefCell::new(None)),
+        };
+
+        // event definition, when show button is clicked or entry is activated
+        let show = {
+            let view = view.clone();
+            let config = config.clone();
+            let token = token.clone();
+            let errorbar = errorbar.clone();
+
+            Rc::new(move || {
+                let client = ::api::client(&config, &token);
+                match view.show(&client, config.toml.likes_timeline.limits.get() as i32) {
+                    Ok(_) => errorbar.hide(),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        errorbar.show_error(&err);
+                    },
+                }
+            })
+        };
+        {
+            let show = show.clone();
+            entry_user.connect_activate(move |_| show());
+        }
+        button_show.connect_clicked(move |_| show());
+
+        // event definition, when older button is clicked
+        {
+            let view = view.clone();
+
+            button_older.connect_clicked(move |_| {
+                let client = ::api::client(&config, &token);
+                match view.older(&client, config.toml.likes_timeline.limits.get() as i32) {
+                    Ok(_) => errorbar.hide(),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        errorbar.show_error(&err);
+                    },
+                }
+            });
+        }
+
+        // event definition, when listboxrow is selected
+        {
+            let view = view.clone();
+
+            view.listbox.clone().connect_row_selected(move |_, listboxrow| {
+                let mut others = view.others.borrow_mut();
+                match *others {
+                    Some((_, ref mut timeline)) => {
+                        ::timeline::home::select_row(listboxrow, timeline);
+                    },
+                    None => {
+                        let mut guard = match view.timeline.lock() {
+                            Ok(guard) => guard,
+                            Err(poisoned) => poisoned.into_inner(),
+                        };
+                        ::timeline::home::select_row(listboxrow, guard.deref_mut());
+                    },
+                }
+            });
+        }
+        view
+    }
+
+    // render shows the cached likes of the authenticated user
+    pub fn render(&self) {
+        *self.others.borrow_mut() = None;
+        let guard = match self.timeline.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match ::timeline::home::update_home(&self.listbox, &guard, false, false) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+    }
+
+    // show fetches the first page of likes of the user in entry
+    pub fn show(&self, client: &::api::Client, count: i32) -> Result<(), ::api::ApiError> {
+        let screen_name = self.entry_user.get_text().unwrap_or(String::new());
+        let screen_name = screen_name.trim().trim_left_matches('@');
+        if screen_name.is_empty() {
+            let likes = try!(likes_timeline(client, None, None, None, count));
+            {
+                let mut guard = match self.timeline.lock() {
+                    Ok(guard) => guard,
+                    Err(poisoned) => poisoned.into_inner(),
+                };
+                *guard = likes;
+            }
+            self.render();
+            return Ok(());
+        }
+
+        let likes = try!(likes_timeline(client, Some(screen_name), None, None, count));
+        match ::timeline::home::update_home(&self.listbox, &likes, false, false) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+        *self.others.borrow_mut() = Some((screen_name.to_owned(), likes));
+        Ok(())
+    }
+
+    // older fetches the page of likes older than the bottom row and appends it
+    pub fn older(&self, client: &::api::Client, count: i32) -> Result<(), ::api::ApiError> {
+        let mut others = self.others.borrow_mut();
+        let mut guard = match self.timeline.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let (screen_name, timeline) = match *others {
+            Some((ref screen_name, ref mut timeline)) => (Some(screen_name.as_str()), timeline),
+            None => (None, guard.deref_mut()),
+        };
+        let max_id = timeline.last().map(|row| row.tweet.id - 1);
+        let likes = try!(likes_timeline(client, screen_name, None, max_id, count));
+        match ::timeline::home::append_home(&self.listbox, &likes) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+        timeline.extend(likes);
+        Ok(())
+    }
+}
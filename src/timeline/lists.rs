@@ -0,0 +1,366 @@
+extern crate egg_mode;
+extern crate rustc_serialize;
+
+use gtk;
+use gtk::prelude::*;
+use rustc_serialize::json;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::mem;
+use std::path;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use timeline::home::TimelineRow;
+
+// lists requests owned and subscribed lists of the authenticated user, and returns their ids and full names
+pub fn lists(client: &::api::Client) -> Result<Vec<(u64, String)>, ::api::ApiError> {
+    let mut lists: Vec<(u64, String)> = Vec::new();
+    for path in &["lists/ownerships.json", "lists/subscriptions.json"] {
+        let mut cursor = "-1".to_owned();
+        while cursor != "0" {
+            let mut params = BTreeMap::new();
+            params.insert("count".to_owned(), "1000".to_owned());
+            params.insert("cursor".to_owned(), cursor.clone());
+            let response = try!(client.get(path, &params));
+            let items = try!(::timeline::home::field(&response, "lists").and_then(|v| {
+                v.as_array().ok_or(format!("lists of {} is not an array", path))
+            }));
+            try!(merge_lists(&mut lists, items));
+            cursor = try!(::timeline::home::string_field(&response, "next_cursor_str"));
+        }
+    }
+    Ok(lists)
+}
+
+// merge_lists appends ids and full names of list objects in items, a list both owned and subscribed is added once
+fn merge_lists(lists: &mut Vec<(u64, String)>, items: &Vec<json::Json>) -> Result<(), ::api::ApiError> {
+    for list in items {
+        let id = try!(::timeline::home::field(list, "id").and_then(|v| {
+            v.as_u64().ok_or("id is not a number".to_owned())
+        }));
+        if !lists.iter().any(|&(known, _)| known == id) {
+            lists.push((id, try!(::timeline::home::string_field(list, "full_name"))));
+        }
+    }
+    Ok(())
+}
+
+// list_timeline requests tweets of the list
+pub fn list_timeline(
+    client: &::api::Client,
+    list_id: u64,
+    since_id: Option<u64>,
+    count: i32,
+) -> Result<Vec<TimelineRow>, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("list_id".to_owned(), format!("{}", list_id));
+    params.insert("count".to_owned(), format!("{}", count));
+    params.insert("tweet_mode".to_owned(), "extended".to_owned());
+    if let Some(since_id) = since_id {
+        params.insert("since_id".to_owned(), format!("{}", since_id));
+    }
+    ::timeline::utils::statuses(client, "lists/statuses.json", &params)
+}
+
+// name returns the name of side pane row and stack child of the list
+pub fn name(id: u64) -> String {
+    format!("{}{}", ::vars::CACHE_LIST, id)
+}
+
+// cache_filename returns the cache file name of the list
+pub fn cache_filename(id: u64) -> String {
+    format!("{}{}.json", ::vars::CACHE_LIST, id)
+}
+
+// ListPane is the side pane row and the timeline of a list
+struct ListPane {
+    id: u64,
+    full_name: String,
+    row: gtk::ListBoxRow,
+    label: gtk::Label,
+    scrolled_window: gtk::ScrolledWindow,
+    listbox: gtk::ListBox,
+    timeline: Vec<TimelineRow>,
+}
+
+// ListsView shows lists under the List header of side pane, each list is opened as a timeline of stack
+#[derive(Clone)]
+pub struct ListsView {
+    pub header: gtk::ListBoxRow,
+    side_listbox: gtk::ListBox,
+    stack: gtk::Stack,
+    panes: Rc<RefCell<Vec<ListPane>>>,
+    config: Arc<::config::Config>,
+    cache_dir: path::PathBuf,
+}
+
+impl ListsView {
+    // new appends the List header to side_listbox, lists are appended after it by load
+    pub fn new(
+        side_listbox: &gtk::ListBox,
+        stack: &gtk::Stack,
+        config: Arc<::config::Config>,
+        token: Arc<Mutex<egg_mode::Token<'static>>>,
+        errorbar: ::errorbar::ErrorBar,
+        cache_dir: path::PathBuf,
+    ) -> ListsView {
+        let list_label = gtk::Label::new(Some("<b>List</b>"));
+        list_label.set_padding(16, 6);
+        list_label.set_xalign(0.0);
+        list_label.set_use_markup(true);
+        let button_reload = gtk::Button::new_from_icon_name("view-refresh", 1);
+        button_reload.set_relief(gtk::ReliefStyle::None);
+
+        let box_header = gtk::Box::new(gtk::Orientation::Horizontal, 2);
+        box_header.pack_start(&list_label, true, true, 0);
+        box_header.pack_start(&button_reload, false, false, 0);
+
+        let header = gtk::ListBoxRow::new();
+        header.add(&box_header);
+        header.set_selectable(false);
+        header.set_can_focus(false);
+        side_listbox.insert(&header, -1);
+
+        let view = ListsView {
+            header: header,
+            side_listbox: side_listbox.clone(),
+            stack: stack.clone(),
+            panes: Rc::new(RefCell::new(Vec::new())),
+            config: config.clone(),
+            cache_dir: cache_dir,
+        };
+
+        // event definition, when reload button is clicked
+        {
+            let view = view.clone();
+            let config = config.clone();
+            let token = token.clone();
+            let errorbar = errorbar.clone();
+
+            button_reload.connect_clicked(move |_| {
+                let client = ::api::client(&config, &token);
+                match view.reload(&client) {
+                    Ok(_) => errorbar.hide(),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        errorbar.show_error(&err);
+                    },
+                }
+            });
+        }
+
+        // event definition, when list is opened from side pane
+        {
+            let view = view.clone();
+
+            side_listbox.connect_row_selected(move |_, listboxrow| {
+                let name = match listboxrow.clone().and_then(|listboxrow| listboxrow.get_name()) {
+                    Some(name) => name,
+                    None => return,
+                };
+                let id = match view.panes.borrow().iter().find(|pane| self::name(pane.id) == name) {
+                    Some(pane) => pane.id,
+                    None => return,
+                };
+                let client = ::api::client(&config, &token);
+                match view.fetch(&client, id) {
+                    Ok(_) => errorbar.hide(),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        errorbar.show_error(&err);
+                    },
+                }
+            });
+        }
+        view
+    }
+
+    // load replaces panes by lists of the active account and their caches
+    pub fn load(&self) {
+        let panes = mem::replace(&mut *self.panes.borrow_mut(), Vec::new());
+        for pane in panes {
+            self.side_listbox.remove(&pane.row);
+            self.stack.remove(&pane.scrolled_window);
+        }
+
+        for list in self.config.lists() {
+            let cache = ::cache::path(&self.cache_dir, &cache_filename(list.id), &self.config.account());
+            let timeline: Vec<TimelineRow> = match ::cache::load(cache) {
+                Ok(timeline) => timeline,
+                Err(err) => {
+                    error!("{:?}", err);
+                    Vec::new()
+                },
+            };
+
+            let label = gtk::Label::new(Some(::timeline::utils::unread_label(&list.full_name, &timeline).as_str()));
+            label.set_padding(16, 6);
+            label.set_xalign(0.0);
+            let row = gtk::ListBoxRow::new();
+            row.set_name(&name(list.id));
+            row.add(&label);
+            self.side_listbox.insert(&row, -1);
+
+            let scrolled_window = gtk::ScrolledWindow::new(None, None);
+            let listbox = gtk::ListBox::new();
+            scrolled_window.add(&listbox);
+            self.stack.add_named(&scrolled_window, &name(list.id));
+            match ::timeline::home::update_home(&listbox, &timeline, false, false) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+
+            // event definition, when listboxrow is selected
+            {
+                let view = self.clone();
+                let id = list.id;
+
+                listbox.connect_row_selected(move |_, listboxrow| {
+                    let mut panes = view.panes.borrow_mut();
+                    let pane = match panes.iter_mut().find(|pane| pane.id == id) {
+                        Some(pane) => pane,
+                        None => return,
+                    };
+                    // update last_read_id of the list
+                    if let Some(read_id) = ::timeline::home::select_row(listboxrow, &mut pane.timeline) {
+                        view.cursor(id, |timeline| timeline.last_read_id.set(read_id));
+                    }
+                    pane.label.set_text(&::timeline::utils::unread_label(&pane.full_name, &pane.timeline));
+                });
+            }
+
+            self.panes.borrow_mut().push(ListPane {
+                id: list.id,
+                full_name: list.full_name,
+                row: row,
+                label: label,
+                scrolled_window: scrolled_window,
+                listbox: listbox,
+                timeline: timeline,
+            });
+        }
+        self.side_listbox.show_all();
+        self.stack.show_all();
+    }
+
+    // reload requests lists, and rebuilds panes keeping cursors and caches of known lists
+    pub fn reload(&self, client: &::api::Client) -> Result<(), ::api::ApiError> {
+        let lists = try!(lists(client));
+        self.write(&self.config.account());
+        self.config.set_lists(lists);
+        self.load();
+        Ok(())
+    }
+
+    // fetch requests tweets of the list newer than its cursor and prepends them
+    pub fn fetch(&self, client: &::api::Client, id: u64) -> Result<(), ::api::ApiError> {
+        let (since_id, limits) = match self.cursor(id, |timeline| {
+            (timeline.last_update_id.get(), timeline.limits.get())
+        }) {
+            Some(cursor) => cursor,
+            None => return Ok(()),
+        };
+        let tweets = try!(list_timeline(client, id, Some(since_id), limits as i32));
+        if let Some(status) = tweets.first() {
+            self.cursor(id, |timeline| timeline.last_update_id.set(status.tweet.id));
+        }
+
+        let mut panes = self.panes.borrow_mut();
+        if let Some(pane) = panes.iter_mut().find(|pane| pane.id == id) {
+            ::timeline::utils::prepend(&mut pane.timeline, &tweets, limits);
+            pane.label.set_text(&::timeline::utils::unread_label(&pane.full_name, &pane.timeline));
+            match ::timeline::home::update_home(&pane.listbox, &tweets, true, false) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+        }
+        Ok(())
+    }
+
+    // fetch_all fetches every list
+    pub fn fetch_all(&self, client: &::api::Client) -> Result<(), ::api::ApiError> {
+        let ids: Vec<u64> = self.panes.borrow().iter().map(|pane| pane.id).collect();
+        for id in ids {
+            try!(self.fetch(client, id));
+        }
+        Ok(())
+    }
+
+    // render renders timelines of lists again, it is called when the mute list is changed
+    pub fn render(&self) {
+        for pane in self.panes.borrow().iter() {
+            pane.label.set_text(&::timeline::utils::unread_label(&pane.full_name, &pane.timeline));
+            match ::timeline::home::update_home(&pane.listbox, &pane.timeline, false, false) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+        }
+    }
+
+    // write writes timelines of lists to the caches of account
+    pub fn write(&self, account: &str) {
+        for pane in self.panes.borrow().iter() {
+            let cache = ::cache::path(&self.cache_dir, &cache_filename(pane.id), account);
+            match ::cache::write(cache, &pane.timeline) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+        }
+    }
+
+    // switch writes caches of the previous account and loads lists of the switched account
+    pub fn switch(&self, from: &str) {
+        self.write(from);
+        self.load();
+    }
+
+    // cursor applies f to the cursor of the list in config
+    fn cursor<T, F: FnOnce(&::config::Timeline) -> T>(&self, id: u64, f: F) -> Option<T> {
+        match *self.config.toml.lists.borrow() {
+            Some(ref lists) => lists.iter().find(|list| list.id == id).map(|list| f(&list.timeline)),
+            None => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items(body: &str) -> Vec<json::Json> {
+        json::Json::from_str(body).unwrap().as_array().unwrap().clone()
+    }
+
+    #[test]
+    fn merge_lists_skips_known_ids() {
+        let mut lists = Vec::new();
+        merge_lists(&mut lists, &items(r#"[{"id": 1, "full_name": "@me/a"}, {"id": 2, "full_name": "@me/b"}]"#))
+            .unwrap();
+        merge_lists(&mut lists, &items(r#"[{"id": 2, "full_name": "@me/b"}, {"id": 3, "full_name": "@you/c"}]"#))
+            .unwrap();
+        assert_eq!(
+            lists,
+            vec![(1, "@me/a".to_owned()), (2, "@me/b".to_owned()), (3, "@you/c".to_owned())]
+        );
+    }
+
+    #[test]
+    fn merge_lists_rejects_list_without_id() {
+        let mut lists = Vec::new();
+        assert!(merge_lists(&mut lists, &items(r#"[{"full_name": "@me/a"}]"#)).is_err());
+    }
+
+    #[test]
+    fn set_lists_keeps_cursors_of_known_lists() {
+        let config = ::config::tests::config("lists", "");
+        config.set_lists(vec![(1, "@me/a".to_owned())]);
+        if let Some(ref lists) = *config.toml.lists.borrow() {
+            lists[0].timeline.last_update_id.set(100);
+        }
+        config.set_lists(vec![(2, "@me/b".to_owned()), (1, "@me/a".to_owned())]);
+        let lists = config.lists();
+        assert_eq!(lists.iter().map(|list| list.id).collect::<Vec<_>>(), vec![2, 1]);
+        assert_eq!(lists[0].timeline.last_update_id.get(), 1);
+        assert_eq!(lists[1].timeline.last_update_id.get(), 100);
+    }
+}
@@ -0,0 +1,18 @@
+extern crate rustc_serialize;
+
+use std::collections::BTreeMap;
+use timeline::home::TimelineRow;
+
+pub fn mentions_timeline(
+    client: &::api::Client,
+    since_id: Option<u64>,
+    count: i32,
+) -> Result<Vec<TimelineRow>, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("count".to_owned(), format!("{}", count));
+    params.insert("tweet_mode".to_owned(), "extended".to_owned());
+    if let Some(since_id) = since_id {
+        params.insert("since_id".to_owned(), format!("{}", since_id));
+    }
+    ::timeline::utils::statuses(client, "statuses/mentions_timeline.json", &params)
+}
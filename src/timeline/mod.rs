@@ -1,2 +1,21 @@
+pub mod actions;
+pub mod dm;
 pub mod home;
+pub mod likes;
+pub mod lists;
+pub mod mentions;
+pub mod profile;
+pub mod search;
+pub mod thread;
 pub mod utils;
+
+// Kind identifies the timeline which a fetched result belongs to
+#[derive(Clone, Debug, PartialEq)]
+pub enum Kind {
+    Home,
+    Mentions,
+    // DirectMessages carries the screen name of the conversation partner
+    DirectMessages(String),
+    // Outbox carries tweets posted from the outbox
+    Outbox,
+}
@@ -0,0 +1,246 @@
+extern crate egg_mode;
+extern crate rustc_serialize;
+
+use gtk;
+use gtk::prelude::*;
+use rustc_serialize::json;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use timeline::home::TimelineRow;
+
+// Profile is the user object shown in the profile pane
+#[derive(Clone, Debug)]
+pub struct Profile {
+    pub screen_name: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub profile_image_url: String,
+    pub profile_banner_url: Option<String>,
+    pub followers_count: u64,
+    pub friends_count: u64,
+    pub statuses_count: u64,
+    pub verified: bool,
+    pub protected: bool,
+}
+
+impl Profile {
+    pub fn from_json(user: &json::Json) -> Result<Profile, ::api::ApiError> {
+        let count = |key: &str| -> Result<u64, String> {
+            ::timeline::home::field(user, key).and_then(|v| v.as_u64().ok_or(format!("{} is not a number", key)))
+        };
+        let flag = |key: &str| user.find(key).and_then(|v| v.as_boolean()).unwrap_or(false);
+        Ok(Profile {
+            screen_name: try!(::timeline::home::string_field(user, "screen_name")),
+            name: try!(::timeline::home::string_field(user, "name")),
+            description: user.find("description").and_then(|v| v.as_string()).map(|s| s.to_owned()),
+            profile_image_url: try!(::timeline::home::string_field(user, "profile_image_url")),
+            profile_banner_url: user.find("profile_banner_url").and_then(|v| v.as_string()).map(|s| s.to_owned()),
+            followers_count: try!(count("followers_count")),
+            friends_count: try!(count("friends_count")),
+            statuses_count: try!(count("statuses_count")),
+            verified: flag("verified"),
+            protected: flag("protected"),
+        })
+    }
+}
+
+// users_show requests the profile of screen_name
+pub fn users_show(client: &::api::Client, screen_name: &str) -> Result<Profile, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("screen_name".to_owned(), screen_name.to_owned());
+    Profile::from_json(&try!(client.get("users/show.json", &params)))
+}
+
+// user_timeline requests tweets of screen_name older than max_id, rows are already read
+pub fn user_timeline(
+    client: &::api::Client,
+    screen_name: &str,
+    max_id: Option<u64>,
+    count: i32,
+) -> Result<Vec<TimelineRow>, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("screen_name".to_owned(), screen_name.to_owned());
+    params.insert("count".to_owned(), format!("{}", count));
+    params.insert("tweet_mode".to_owned(), "extended".to_owned());
+    if let Some(max_id) = max_id {
+        params.insert("max_id".to_owned(), format!("{}", max_id));
+    }
+    let mut timeline = try!(::timeline::utils::statuses(client, "statuses/user_timeline.json", &params));
+    for row in timeline.iter_mut() {
+        row.unread = false;
+    }
+    Ok(timeline)
+}
+
+// ProfileView shows the profile of a user and the user timeline
+#[derive(Clone)]
+pub struct ProfileView {
+    pub container: gtk::Box,
+    image_banner: gtk::Image,
+    image_avatar: gtk::Image,
+    label_name: gtk::Label,
+    image_verified: gtk::Image,
+    image_protected: gtk::Image,
+    label_description: gtk::Label,
+    label_counts: gtk::Label,
+    listbox: gtk::ListBox,
+    screen_name: Rc<RefCell<Option<String>>>,
+    timeline: Rc<RefCell<Vec<TimelineRow>>>,
+}
+
+impl ProfileView {
+    pub fn new(
+        config: Arc<::config::Config>,
+        token: Arc<Mutex<egg_mode::Token<'static>>>,
+        errorbar: ::errorbar::ErrorBar,
+    ) -> ProfileView {
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        let image_banner = gtk::Image::new();
+
+        let image_avatar = gtk::Image::new();
+        image_avatar.set_padding(8, 8);
+        let label_name = gtk::Label::new(None);
+        label_name.set_selectable(true);
+        label_name.set_xalign(0.0);
+        let image_verified = gtk::Image::new_from_icon_name("emblem-ok-symbolic", 1);
+        image_verified.set_tooltip_text(Some("verified"));
+        let image_protected = gtk::Image::new_from_icon_name("changes-prevent-symbolic", 1);
+        image_protected.set_tooltip_text(Some("protected"));
+        let label_description = gtk::Label::new(None);
+        label_description.set_selectable(true);
+        label_description.set_line_wrap(true);
+        label_description.set_xalign(0.0);
+        let label_counts = gtk::Label::new(None);
+        label_counts.set_xalign(0.0);
+
+        let box_name = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        box_name.pack_start(&label_name, false, false, 0);
+        box_name.pack_start(&image_verified, false, false, 0);
+        box_name.pack_start(&image_protected, false, false, 0);
+
+        let box_label = gtk::Box::new(gtk::Orientation::Vertical, 2);
+        box_label.pack_start(&box_name, false, false, 0);
+        box_label.pack_start(&label_description, false, false, 0);
+        box_label.pack_start(&label_counts, false, false, 0);
+
+        let box_header = gtk::Box::new(gtk::Orientation::Horizontal, 2);
+        box_header.pack_start(&image_avatar, false, false, 0);
+        box_header.pack_start(&box_label, true, true, 0);
+
+        let scrolled_window = gtk::ScrolledWindow::new(None, None);
+        let box_scrolled = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        let listbox = gtk::ListBox::new();
+        let button_older = gtk::Button::new_with_label("Load older tweets");
+        box_scrolled.pack_start(&listbox, false, false, 0);
+        box_scrolled.pack_start(&button_older, false, false, 4);
+        scrolled_window.add(&box_scrolled);
+
+        container.pack_start(&image_banner, false, false, 0);
+        container.pack_start(&box_header, false, false, 0);
+        container.pack_start(&scrolled_window, true, true, 0);
+
+        let view = ProfileView {
+            container: container,
+            image_banner: image_banner,
+            image_avatar: image_avatar,
+            label_name: label_name,
+            image_verified: image_verified,
+            image_protected: image_protected,
+            label_description: label_description,
+            label_counts: label_counts,
+            listbox: listbox,
+            screen_name: Rc::new(RefCell::new(None)),
+            timeline: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        // event definition, when older button is clicked
+        {
+            let view = view.clone();
+
+            button_older.connect_clicked(move |_| {
+                let client = ::api::client(&config, &token);
+                match view.older(&client, ::vars::PAGE_SIZE as i32) {
+                    Ok(_) => errorbar.hide(),
+                    Err(err) => {
+                        error!("{:?}", err);
+                        errorbar.show_error(&err);
+                    },
+                }
+            });
+        }
+
+        // event definition, when listboxrow is selected
+        {
+            let view = view.clone();
+
+            view.listbox.clone().connect_row_selected(move |_, listboxrow| {
+                ::timeline::home::select_row(listboxrow, &mut view.timeline.borrow_mut());
+            });
+        }
+        view
+    }
+
+    // show requests the profile and the first page of the user timeline of screen_name
+    pub fn show(&self, client: &::api::Client, screen_name: &str, count: i32) -> Result<(), ::api::ApiError> {
+        let profile = try!(users_show(client, screen_name));
+        let timeline = try!(user_timeline(client, screen_name, None, count));
+        self.render_profile(&profile);
+        match ::timeline::home::update_home(&self.listbox, &timeline, false, false) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+        *self.screen_name.borrow_mut() = Some(profile.screen_name);
+        *self.timeline.borrow_mut() = timeline;
+        Ok(())
+    }
+
+    // older requests the page older than the bottom row and appends it
+    pub fn older(&self, client: &::api::Client, count: i32) -> Result<(), ::api::ApiError> {
+        let screen_name = match self.screen_name.borrow().clone() {
+            Some(screen_name) => screen_name,
+            None => return Ok(()),
+        };
+        let max_id = self.timeline.borrow().last().map(|row| row.tweet.id - 1);
+        let tweets = try!(user_timeline(client, &screen_name, max_id, count));
+        match ::timeline::home::append_home(&self.listbox, &tweets) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+        self.timeline.borrow_mut().extend(tweets);
+        Ok(())
+    }
+
+    fn render_profile(&self, profile: &Profile) {
+        match profile.profile_banner_url {
+            Some(ref url) => {
+                match ::utils::get_profile_image(&format!("{}/600x200", url)) {
+                    Ok(filename) => self.image_banner.set_from_file(filename),
+                    Err(err) => error!("{:?}", err),
+                }
+            },
+            None => self.image_banner.clear(),
+        }
+        // the bigger avatar is 73x73 while the url of user object is the normal one
+        match ::utils::get_profile_image(&profile.profile_image_url.replace("_normal", "_bigger")) {
+            Ok(filename) => self.image_avatar.set_from_file(filename),
+            Err(err) => error!("{:?}", err),
+        }
+
+        self.label_name.set_markup(&format!(
+            "<b>{}</b> @{}",
+            ::timeline::utils::escape_markup(&profile.name),
+            profile.screen_name
+        ));
+        self.image_verified.set_visible(profile.verified);
+        self.image_protected.set_visible(profile.protected);
+        self.label_description.set_text(&profile.description.clone().unwrap_or(String::new()));
+        self.label_counts.set_text(&format!(
+            "{} Tweets   {} Following   {} Followers",
+            profile.statuses_count,
+            profile.friends_count,
+            profile.followers_count
+        ));
+    }
+}
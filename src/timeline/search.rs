@@ -0,0 +1,446 @@
+extern crate crypto;
+extern crate egg_mode;
+extern crate rustc_serialize;
+
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+use gtk;
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::mem;
+use std::path;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use timeline::home::TimelineRow;
+
+// search requests recent tweets matching query
+pub fn search(
+    client: &::api::Client,
+    query: &str,
+    since_id: Option<u64>,
+    max_id: Option<u64>,
+    count: i32,
+) -> Result<Vec<TimelineRow>, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("q".to_owned(), query.to_owned());
+    params.insert("count".to_owned(), format!("{}", count));
+    params.insert("tweet_mode".to_owned(), "extended".to_owned());
+    params.insert("result_type".to_owned(), "recent".to_owned());
+    if let Some(since_id) = since_id {
+        params.insert("since_id".to_owned(), format!("{}", since_id));
+    }
+    if let Some(max_id) = max_id {
+        params.insert("max_id".to_owned(), format!("{}", max_id));
+    }
+    let response = try!(client.get("search/tweets.json", &params));
+    let statuses = try!(::timeline::home::field(&response, "statuses").and_then(|v| {
+        v.as_array().ok_or("statuses is not an array".to_owned())
+    }));
+    let mut timeline: Vec<TimelineRow> = Vec::new();
+    for status in statuses {
+        timeline.push(try!(TimelineRow::from_json(status)));
+    }
+    Ok(timeline)
+}
+
+// name returns the name of side pane row and stack child of the saved search
+pub fn name(query: &str) -> String {
+    let mut sha256 = Sha256::new();
+    sha256.input_str(query);
+    format!("{}{}", ::vars::CACHE_SEARCH, &sha256.result_str()[..16])
+}
+
+// cache_filename returns the cache file name of the saved search
+pub fn cache_filename(query: &str) -> String {
+    format!("{}.json", name(query))
+}
+
+// SearchPane is the side pane row and the timeline of a saved search
+struct SearchPane {
+    query: String,
+    row: gtk::ListBoxRow,
+    label: gtk::Label,
+    scrolled_window: gtk::ScrolledWindow,
+    listbox: gtk::ListBox,
+    timeline: Vec<TimelineRow>,
+}
+
+// SearchView runs queries in the search page, and shows saved searches under the Search header of side pane
+#[derive(Clone)]
+pub struct SearchView {
+    pub header: gtk::ListBoxRow,
+    pub container: gtk::Box,
+    row_search: gtk::ListBoxRow,
+    entry_query: gtk::Entry,
+    listbox: gtk::ListBox,
+    // query and results of the search page
+    query: Rc<RefCell<Option<String>>>,
+    results: Rc<RefCell<Vec<TimelineRow>>>,
+    side_listbox: gtk::ListBox,
+    stack: gtk::Stack,
+    panes: Rc<RefCell<Vec<SearchPane>>>,
+    config: Arc<::config::Config>,
+    token: Arc<Mutex<egg_mode::Token<'static>>>,
+    errorbar: ::errorbar::ErrorBar,
+    cache_dir: path::PathBuf,
+}
+
+impl SearchView {
+    // new appends the Search header and the search page row to side_listbox, saved searches follow them
+    pub fn new(
+        side_listbox: &gtk::ListBox,
+        stack: &gtk::Stack,
+        config: Arc<::config::Config>,
+        token: Arc<Mutex<egg_mode::Token<'static>>>,
+        errorbar: ::errorbar::ErrorBar,
+        cache_dir: path::PathBuf,
+    ) -> SearchView {
+        let search_label = gtk::Label::new(Some("<b>Search</b>"));
+        search_label.set_padding(16, 6);
+        search_label.set_xalign(0.0);
+        search_label.set_use_markup(true);
+        let header = gtk::ListBoxRow::new();
+        header.add(&search_label);
+        header.set_selectable(false);
+        header.set_can_focus(false);
+        side_listbox.insert(&header, -1);
+
+        let new_search_label = gtk::Label::new(Some("New search"));
+        new_search_label.set_padding(16, 6);
+        new_search_label.set_xalign(0.0);
+        let row_search = gtk::ListBoxRow::new();
+        row_search.set_name("search");
+        row_search.add(&new_search_label);
+        side_listbox.insert(&row_search, -1);
+
+        // search page
+        let container = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        let box_query = gtk::Box::new(gtk::Orientation::Horizontal, 2);
+        box_query.set_border_width(4);
+        let entry_query = gtk::Entry::new();
+        entry_query.set_placeholder_text(Some("query"));
+        let button_search = gtk::Button::new_with_label("Search");
+        let button_pin = gtk::Button::new_with_label("Save");
+        box_query.pack_start(&entry_query, true, true, 0);
+        box_query.pack_start(&button_search, false, false, 0);
+        box_query.pack_start(&button_pin, false, false, 0);
+
+        let scrolled_window = gtk::ScrolledWindow::new(None, None);
+        let box_scrolled = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        let listbox = gtk::ListBox::new();
+        let button_older = gtk::Button::new_with_label("Load older results");
+        box_scrolled.pack_start(&listbox, false, false, 0);
+        box_scrolled.pack_start(&button_older, false, false, 4);
+        scrolled_window.add(&box_scrolled);
+
+        container.pack_start(&box_query, false, false, 0);
+        container.pack_start(&scrolled_window, true, true, 0);
+        stack.add_named(&container, "search");
+
+        let view = SearchView {
+            header: header,
+            container: container,
+            row_search: row_search,
+            entry_query: entry_query.clone(),
+            listbox: listbox,
+            query: Rc::new(RefCell::new(None)),
+            results: Rc::new(RefCell::new(Vec::new())),
+            side_listbox: side_listbox.clone(),
+            stack: stack.clone(),
+            panes: Rc::new(RefCell::new(Vec::new())),
+            config: config,
+            token: token,
+            errorbar: errorbar,
+            cache_dir: cache_dir,
+        };
+
+        // event definition, when search button is clicked or query entry is activated
+        let run = {
+            let view = view.clone();
+
+            Rc::new(move || {
+                let query = view.entry_query.get_text().unwrap_or(String::new());
+                if query.trim().is_empty() {
+                    return;
+                }
+                let result = view.run(query.trim());
+                view.report(result);
+            })
+        };
+        {
+            let run = run.clone();
+            entry_query.connect_activate(move |_| run());
+        }
+        button_search.connect_clicked(move |_| run());
+
+        // event definition, when save button is clicked
+        {
+            let view = view.clone();
+
+            button_pin.connect_clicked(move |_| {
+                let query = match view.query.borrow().clone() {
+                    Some(query) => query,
+                    None => return,
+                };
+                if view.config.add_search(&query) {
+                    view.write(&view.config.account());
+                    view.load();
+                }
+            });
+        }
+
+        // event definition, when older button is clicked
+        {
+            let view = view.clone();
+
+            button_older.connect_clicked(move |_| {
+                let result = view.older();
+                view.report(result);
+            });
+        }
+
+        // event definition, when listboxrow of the search page is selected
+        {
+            let view = view.clone();
+
+            view.listbox.clone().connect_row_selected(move |_, listboxrow| {
+                ::timeline::home::select_row(listboxrow, &mut view.results.borrow_mut());
+            });
+        }
+
+        // event definition, when saved search is opened from side pane
+        {
+            let view = view.clone();
+
+            side_listbox.connect_row_selected(move |_, listboxrow| {
+                let name = match listboxrow.clone().and_then(|listboxrow| listboxrow.get_name()) {
+                    Some(name) => name,
+                    None => return,
+                };
+                let query = match view.panes.borrow().iter().find(|pane| self::name(&pane.query) == name) {
+                    Some(pane) => pane.query.clone(),
+                    None => return,
+                };
+                let client = ::api::client(&view.config, &view.token);
+                let result = view.fetch(&client, &query);
+                view.report(result);
+            });
+        }
+        view
+    }
+
+    // open runs query in the search page and shows it
+    pub fn open(&self, query: &str) {
+        self.entry_query.set_text(query);
+        let result = self.run(query);
+        self.report(result);
+        self.side_listbox.select_row(Some(&self.row_search));
+    }
+
+    // run requests the first page of query into the search page
+    pub fn run(&self, query: &str) -> Result<(), ::api::ApiError> {
+        let client = ::api::client(&self.config, &self.token);
+        let results = try!(search(&client, query, None, None, self.count()));
+        match ::timeline::home::update_home(&self.listbox, &results, false, false) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+        *self.query.borrow_mut() = Some(query.to_owned());
+        *self.results.borrow_mut() = results;
+        Ok(())
+    }
+
+    // older requests the page older than the bottom row of the search page and appends it
+    pub fn older(&self) -> Result<(), ::api::ApiError> {
+        let query = match self.query.borrow().clone() {
+            Some(query) => query,
+            None => return Ok(()),
+        };
+        let max_id = self.results.borrow().last().map(|row| row.tweet.id - 1);
+        let client = ::api::client(&self.config, &self.token);
+        let results = try!(search(&client, &query, None, max_id, self.count()));
+        match ::timeline::home::append_home(&self.listbox, &results) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+        self.results.borrow_mut().extend(results);
+        Ok(())
+    }
+
+    // load replaces saved search panes by saved searches of the active account and their caches
+    pub fn load(&self) {
+        let panes = mem::replace(&mut *self.panes.borrow_mut(), Vec::new());
+        for pane in panes {
+            self.side_listbox.remove(&pane.row);
+            self.stack.remove(&pane.scrolled_window);
+        }
+
+        for (index, saved) in self.config.searches().into_iter().enumerate() {
+            let cache = ::cache::path(&self.cache_dir, &cache_filename(&saved.query), &self.config.account());
+            let timeline: Vec<TimelineRow> = match ::cache::load(cache) {
+                Ok(timeline) => timeline,
+                Err(err) => {
+                    error!("{:?}", err);
+                    Vec::new()
+                },
+            };
+
+            let label = gtk::Label::new(Some(::timeline::utils::unread_label(&saved.query, &timeline).as_str()));
+            label.set_padding(16, 6);
+            label.set_xalign(0.0);
+            let button_remove = gtk::Button::new_from_icon_name("list-remove", 1);
+            button_remove.set_relief(gtk::ReliefStyle::None);
+            let box_row = gtk::Box::new(gtk::Orientation::Horizontal, 2);
+            box_row.pack_start(&label, true, true, 0);
+            box_row.pack_start(&button_remove, false, false, 0);
+
+            // saved searches are kept right after the search page row
+            let row = gtk::ListBoxRow::new();
+            row.set_name(&name(&saved.query));
+            row.add(&box_row);
+            self.side_listbox.insert(&row, self.row_search.get_index() + 1 + index as i32);
+
+            let scrolled_window = gtk::ScrolledWindow::new(None, None);
+            let listbox = gtk::ListBox::new();
+            scrolled_window.add(&listbox);
+            self.stack.add_named(&scrolled_window, &name(&saved.query));
+            match ::timeline::home::update_home(&listbox, &timeline, false, false) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+
+            // event definition, when remove button is clicked
+            {
+                let view = self.clone();
+                let query = saved.query.clone();
+
+                button_remove.connect_clicked(move |_| {
+                    view.write(&view.config.account());
+                    view.config.remove_search(&query);
+                    view.load();
+                });
+            }
+
+            // event definition, when listboxrow is selected
+            {
+                let view = self.clone();
+                let query = saved.query.clone();
+
+                listbox.connect_row_selected(move |_, listboxrow| {
+                    let mut panes = view.panes.borrow_mut();
+                    let pane = match panes.iter_mut().find(|pane| pane.query == query) {
+                        Some(pane) => pane,
+                        None => return,
+                    };
+                    // update last_read_id of the saved search
+                    if let Some(read_id) = ::timeline::home::select_row(listboxrow, &mut pane.timeline) {
+                        view.cursor(&query, |timeline| timeline.last_read_id.set(read_id));
+                    }
+                    pane.label.set_text(&::timeline::utils::unread_label(&pane.query, &pane.timeline));
+                });
+            }
+
+            self.panes.borrow_mut().push(SearchPane {
+                query: saved.query,
+                row: row,
+                label: label,
+                scrolled_window: scrolled_window,
+                listbox: listbox,
+                timeline: timeline,
+            });
+        }
+        self.side_listbox.show_all();
+        self.stack.show_all();
+    }
+
+    // fetch requests tweets of the saved search newer than its cursor and prepends them
+    pub fn fetch(&self, client: &::api::Client, query: &str) -> Result<(), ::api::ApiError> {
+        let (since_id, limits) = match self.cursor(query, |timeline| {
+            (timeline.last_update_id.get(), timeline.limits.get())
+        }) {
+            Some(cursor) => cursor,
+            None => return Ok(()),
+        };
+        let tweets = try!(search(client, query, Some(since_id), None, limits as i32));
+        if let Some(status) = tweets.first() {
+            self.cursor(query, |timeline| timeline.last_update_id.set(status.tweet.id));
+        }
+
+        let mut panes = self.panes.borrow_mut();
+        if let Some(pane) = panes.iter_mut().find(|pane| pane.query == query) {
+            ::timeline::utils::prepend(&mut pane.timeline, &tweets, limits);
+            pane.label.set_text(&::timeline::utils::unread_label(&pane.query, &pane.timeline));
+            match ::timeline::home::update_home(&pane.listbox, &tweets, true, false) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+        }
+        Ok(())
+    }
+
+    // fetch_all fetches every saved search
+    pub fn fetch_all(&self, client: &::api::Client) -> Result<(), ::api::ApiError> {
+        let queries: Vec<String> = self.panes.borrow().iter().map(|pane| pane.query.clone()).collect();
+        for query in queries {
+            try!(self.fetch(client, &query));
+        }
+        Ok(())
+    }
+
+    // render renders saved searches and the search page again, it is called when the mute list is changed
+    pub fn render(&self) {
+        for pane in self.panes.borrow().iter() {
+            pane.label.set_text(&::timeline::utils::unread_label(&pane.query, &pane.timeline));
+            match ::timeline::home::update_home(&pane.listbox, &pane.timeline, false, false) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+        }
+        match ::timeline::home::update_home(&self.listbox, &self.results.borrow(), false, false) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+    }
+
+    // write writes timelines of saved searches to the caches of account
+    pub fn write(&self, account: &str) {
+        for pane in self.panes.borrow().iter() {
+            let cache = ::cache::path(&self.cache_dir, &cache_filename(&pane.query), account);
+            match ::cache::write(cache, &pane.timeline) {
+                Ok(_) => (),
+                Err(err) => error!("{:?}", err),
+            }
+        }
+    }
+
+    // switch writes caches of the previous account and loads saved searches of the switched account
+    pub fn switch(&self, from: &str) {
+        self.write(from);
+        self.load();
+    }
+
+    fn count(&self) -> i32 {
+        self.config.toml.home_timeline.limits.get() as i32
+    }
+
+    fn report(&self, result: Result<(), ::api::ApiError>) {
+        match result {
+            Ok(_) => self.errorbar.hide(),
+            Err(err) => {
+                error!("{:?}", err);
+                self.errorbar.show_error(&err);
+            },
+        }
+    }
+
+    // cursor applies f to the cursor of the saved search in config
+    fn cursor<T, F: FnOnce(&::config::Timeline) -> T>(&self, query: &str, f: F) -> Option<T> {
+        match *self.config.toml.searches.borrow() {
+            Some(ref searches) => {
+                searches.iter().find(|search| search.query == query).map(|search| f(&search.timeline))
+            },
+            None => None,
+        }
+    }
+}
@@ -0,0 +1,112 @@
+extern crate rustc_serialize;
+
+use gtk;
+use gtk::prelude::*;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use timeline::home::TimelineRow;
+
+// MAX_DEPTH is the maximum number of tweets walked up the reply chain
+const MAX_DEPTH: usize = 50;
+
+// statuses_show requests the tweet of id
+pub fn statuses_show(client: &::api::Client, id: u64) -> Result<TimelineRow, ::api::ApiError> {
+    let mut params = BTreeMap::new();
+    params.insert("id".to_owned(), format!("{}", id));
+    params.insert("tweet_mode".to_owned(), "extended".to_owned());
+    let mut row = try!(TimelineRow::from_json(&try!(client.get("statuses/show.json", &params))));
+    row.unread = false;
+    Ok(row)
+}
+
+// thread returns the reply chain ending at the tweet of id, oldest first, and known replies to it.
+// cached tweets in known are used before requesting the API,
+// the chain stops at a tweet which is not available like a deleted or protected one
+pub fn thread(
+    client: &::api::Client,
+    id: u64,
+    known: &Vec<TimelineRow>,
+) -> Result<(Vec<TimelineRow>, Vec<TimelineRow>), ::api::ApiError> {
+    let lookup = |id: u64| -> Result<TimelineRow, ::api::ApiError> {
+        match known.iter().find(|row| row.tweet.id == id) {
+            Some(row) => Ok(row.clone()),
+            None => statuses_show(client, id),
+        }
+    };
+
+    let mut chain = vec![try!(lookup(id))];
+    while chain.len() < MAX_DEPTH {
+        let parent_id = match chain[0].tweet.in_reply_to() {
+            Some(parent_id) => parent_id,
+            None => break,
+        };
+        match lookup(parent_id) {
+            Ok(parent) => chain.insert(0, parent),
+            Err(err) => {
+                error!("{:?}", err);
+                break;
+            },
+        }
+    }
+
+    // replies and retweets of them reply to the original tweet even when id is of a retweet
+    let status_id = chain[chain.len() - 1].tweet.status_id();
+    let mut replies: Vec<TimelineRow> = known
+        .iter()
+        .filter(|row| row.tweet.in_reply_to() == Some(status_id))
+        .cloned()
+        .collect();
+    replies.sort_by(|a, b| a.tweet.status_id().cmp(&b.tweet.status_id()));
+    replies.dedup_by_key(|row| row.tweet.status_id());
+    Ok((chain, replies))
+}
+
+// ThreadView shows the reply chain of the focused tweet and replies below it
+#[derive(Clone)]
+pub struct ThreadView {
+    pub container: gtk::ScrolledWindow,
+    listbox: gtk::ListBox,
+    timeline: Rc<RefCell<Vec<TimelineRow>>>,
+}
+
+impl ThreadView {
+    pub fn new() -> ThreadView {
+        let container = gtk::ScrolledWindow::new(None, None);
+        let listbox = gtk::ListBox::new();
+        container.add(&listbox);
+
+        let view = ThreadView {
+            container: container,
+            listbox: listbox,
+            timeline: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        // event definition, when listboxrow is selected
+        {
+            let view = view.clone();
+
+            view.listbox.clone().connect_row_selected(move |_, listboxrow| {
+                ::timeline::home::select_row(listboxrow, &mut view.timeline.borrow_mut());
+            });
+        }
+        view
+    }
+
+    // show renders the thread of the tweet of id
+    pub fn show(&self, client: &::api::Client, id: u64, known: &Vec<TimelineRow>) -> Result<(), ::api::ApiError> {
+        let (chain, replies) = try!(thread(client, id, known));
+        let mut timeline = chain;
+        timeline.extend(replies);
+        for row in timeline.iter_mut() {
+            row.unread = false;
+            row.gap = None;
+        }
+        match ::timeline::home::update_home(&self.listbox, &timeline, false, false) {
+            Ok(_) => (),
+            Err(err) => error!("{:?}", err),
+        }
+        *self.timeline.borrow_mut() = timeline;
+        Ok(())
+    }
+}
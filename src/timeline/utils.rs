@@ -1,10 +1,355 @@
 extern crate regex;
+extern crate rustc_serialize;
 
+use gtk;
+use gtk::prelude::*;
 use regex::Regex;
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use timeline::home::{Engagement, TimelineRow, Tweet, User};
+
+// Action is the request of a button on the action bar of the expanded row
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Reply,
+    Retweet,
+    Unretweet,
+    Like,
+    Unlike,
+    Quote,
+    Delete,
+}
+
+thread_local! {
+    // LINK_HANDLERS opens internal links of labels by their scheme, like user:screen_name
+    static LINK_HANDLERS: RefCell<BTreeMap<String, Rc<Fn(&str)>>> = RefCell::new(BTreeMap::new());
+    // ACTION_HANDLER runs actions of the action bar, and returns the engagement after retweet and like
+    static ACTION_HANDLER: RefCell<Option<Rc<Fn(Action, &Tweet) -> Option<Engagement>>>> = RefCell::new(None);
+    // SCREEN_NAME is the screen name of the authenticated user, own tweets can be deleted
+    static SCREEN_NAME: RefCell<Option<String>> = RefCell::new(None);
+    // MUTED is the mute list of config, an entry is a user id or a screen name
+    static MUTED: RefCell<Vec<String>> = RefCell::new(Vec::new());
+    // MUTE_HANDLER adds the user to the mute list, it is called by the context menu of rows
+    static MUTE_HANDLER: RefCell<Option<Rc<Fn(&User)>>> = RefCell::new(None);
+    // MUTE_RULES are mute rules of config with their case-insensitive regexes
+    static MUTE_RULES: RefCell<Vec<(::config::MuteRule, Regex)>> = RefCell::new(Vec::new());
+}
 
 pub fn format_tweet_body(text: &str) -> Result<String, regex::Error> {
     let hyperlink_re = Regex::new(r"(?P<hyperlink>http[s]://[0-9a-zA-Z\./]+)").unwrap();
     let body = format!(r#"{}"#,
                        hyperlink_re.replace_all(text, r#"<a href="$hyperlink">$hyperlink</a>"#));
+    // hashtags are internal links which open the search
+    let hashtag_re = try!(Regex::new(r"(?P<head>^|\s)#(?P<hashtag>\w+)"));
+    let body = hashtag_re.replace_all(&body, r#"$head<a href="hashtag:$hashtag">#$hashtag</a>"#);
     return Ok(body);
 }
+
+// format_user_links makes screen names in text internal links which open the profile
+pub fn format_user_links(text: &str) -> String {
+    let user_re = Regex::new(r"@(?P<screen_name>[0-9a-zA-Z_]+)").unwrap();
+    user_re.replace_all(text, r#"<a href="user:$screen_name">@$screen_name</a>"#)
+}
+
+// register_link_handler registers handler of the internal link scheme, it is called on the gtk thread
+pub fn register_link_handler<F: Fn(&str) + 'static>(scheme: &str, handler: F) {
+    LINK_HANDLERS.with(|handlers| handlers.borrow_mut().insert(scheme.to_owned(), Rc::new(handler)));
+}
+
+// connect_links routes internal links of label to the registered handlers, other links open the browser
+pub fn connect_links(label: &gtk::Label) {
+    label.connect_activate_link(|_, uri| {
+        let (scheme, value) = match uri.find(':') {
+            Some(index) => (&uri[..index], &uri[index + 1..]),
+            None => return Inhibit(false),
+        };
+        let handler = LINK_HANDLERS.with(|handlers| handlers.borrow().get(scheme).cloned());
+        match handler {
+            Some(handler) => {
+                handler(value);
+                Inhibit(true)
+            },
+            None => Inhibit(false),
+        }
+    });
+}
+
+// register_action_handler registers handler of the action bar, it is called on the gtk thread
+pub fn register_action_handler<F: Fn(Action, &Tweet) -> Option<Engagement> + 'static>(handler: F) {
+    ACTION_HANDLER.with(|action_handler| *action_handler.borrow_mut() = Some(Rc::new(handler)));
+}
+
+// run_action runs action on tweet by the registered handler
+pub fn run_action(action: Action, tweet: &Tweet) -> Option<Engagement> {
+    let handler = ACTION_HANDLER.with(|action_handler| action_handler.borrow().clone());
+    match handler {
+        Some(handler) => handler(action, tweet),
+        None => None,
+    }
+}
+
+// set_screen_name sets the screen name of the authenticated user
+pub fn set_screen_name(screen_name: Option<String>) {
+    SCREEN_NAME.with(|own| *own.borrow_mut() = screen_name);
+}
+
+// is_own returns true when tweet is posted by the authenticated user, own retweets are not
+pub fn is_own(tweet: &Tweet) -> bool {
+    tweet.retweeted_status.is_none() &&
+        SCREEN_NAME.with(|own| own.borrow().as_ref() == Some(&tweet.user.screen_name))
+}
+
+// set_muted sets the mute list which rendering and unread counts are filtered by
+pub fn set_muted(muted: Vec<String>) {
+    MUTED.with(|list| *list.borrow_mut() = muted);
+}
+
+// is_muted returns true when tweet is posted by a muted user, or it is the retweet of a tweet of a muted user
+pub fn is_muted(tweet: &Tweet) -> bool {
+    MUTED.with(|list| {
+        list.borrow().iter().any(|entry| is_muted_user(entry, &tweet.user) || is_muted_user(entry, tweet.author()))
+    })
+}
+
+// is_muted_user returns true when entry of the mute list is the id or the screen name of user
+pub fn is_muted_user(entry: &str, user: &User) -> bool {
+    ::mute::is_muted_user(entry, user.id, &user.screen_name)
+}
+
+// set_mute_rules compiles rules which tweets are collapsed by, an invalid regex is skipped
+pub fn set_mute_rules(rules: Vec<::config::MuteRule>) {
+    let compiled = rules
+        .into_iter()
+        .filter_map(|rule| match mute_regex(&rule) {
+            Ok(re) => Some((rule, re)),
+            Err(err) => {
+                error!("{:?}", err);
+                None
+            },
+        })
+        .collect();
+    MUTE_RULES.with(|rules| *rules.borrow_mut() = compiled);
+}
+
+// mute_regex returns the case-insensitive regex of rule, a word or a phrase is matched literally as whole words,
+// a regex pattern is used as it is
+pub fn mute_regex(rule: &::config::MuteRule) -> Result<Regex, regex::Error> {
+    if rule.regex {
+        return Regex::new(&format!("(?i){}", rule.pattern));
+    }
+    // a boundary is required only next to a word character, "#tag" matches after a space too
+    let is_word = |c: Option<char>| c.map(|c| c.is_alphanumeric() || c == '_').unwrap_or(false);
+    let start = match is_word(rule.pattern.chars().next()) {
+        true => r"\b",
+        false => "",
+    };
+    let end = match is_word(rule.pattern.chars().last()) {
+        true => r"\b",
+        false => "",
+    };
+    Regex::new(&format!("(?i){}{}{}", start, regex::quote(&rule.pattern), end))
+}
+
+// mute_reason returns the reason of the first mute rule which tweet matches, the tweet is collapsed by it
+pub fn mute_reason(tweet: &Tweet) -> Option<String> {
+    MUTE_RULES.with(|rules| {
+        rules
+            .borrow()
+            .iter()
+            .find(|&&(ref rule, ref re)| {
+                !rule.is_expired() && (!rule.retweets_only || tweet.retweeted_status.is_some()) &&
+                    (!rule.non_followed_only || !is_followed(tweet.author())) && re.is_match(tweet.full_text())
+            })
+            .map(|&(ref rule, _)| describe_mute_rule(rule))
+    })
+}
+
+// describe_mute_rule returns the description of rule shown in collapsed rows and preferences
+pub fn describe_mute_rule(rule: &::config::MuteRule) -> String {
+    let mut description = match rule.regex {
+        true => format!("/{}/", rule.pattern),
+        false => format!("\"{}\"", rule.pattern),
+    };
+    if rule.retweets_only {
+        description.push_str(" in retweets");
+    }
+    if rule.non_followed_only {
+        description.push_str(" from users not followed");
+    }
+    description
+}
+
+// is_followed returns true when user is followed or the authenticated user, unknown one is regarded as not followed
+fn is_followed(user: &User) -> bool {
+    user.following.unwrap_or(false) || SCREEN_NAME.with(|own| own.borrow().as_ref() == Some(&user.screen_name))
+}
+
+// register_mute_handler registers handler of the mute menu item, it is called on the gtk thread
+pub fn register_mute_handler<F: Fn(&User) + 'static>(handler: F) {
+    MUTE_HANDLER.with(|mute_handler| *mute_handler.borrow_mut() = Some(Rc::new(handler)));
+}
+
+// run_mute mutes user by the registered handler
+pub fn run_mute(user: &User) {
+    let handler = MUTE_HANDLER.with(|mute_handler| mute_handler.borrow().clone());
+    if let Some(handler) = handler {
+        handler(user);
+    }
+}
+
+// statuses requests the timeline of path and converts the statuses into unread rows
+pub fn statuses(
+    client: &::api::Client,
+    path: &str,
+    params: &BTreeMap<String, String>,
+) -> Result<Vec<TimelineRow>, ::api::ApiError> {
+    let mut timeline: Vec<TimelineRow> = Vec::new();
+    let statuses = try!(client.get(path, params));
+    for status in try!(statuses.as_array().ok_or(format!("{} is not an array", path))) {
+        timeline.push(try!(TimelineRow::from_json(status)));
+        debug!("{:?}", status);
+    }
+    Ok(timeline)
+}
+
+// page_size returns the count requested per page of timeline limited to limits rows
+pub fn page_size(limits: usize) -> i32 {
+    cmp::min(limits, ::vars::PAGE_SIZE) as i32
+}
+
+// unread_count returns the number of unread rows in timeline, muted rows are not counted
+pub fn unread_count(timeline: &Vec<TimelineRow>) -> usize {
+    timeline.iter().filter(|row| row.unread && !is_muted(&row.tweet) && mute_reason(&row.tweet).is_none()).count()
+}
+
+// prepend inserts newer rows at the top of timeline and trims it to limit
+pub fn prepend(timeline: &mut Vec<TimelineRow>, rows: &Vec<TimelineRow>, limit: usize) {
+    for (index, row) in rows.iter().enumerate() {
+        timeline.insert(index, row.clone());
+    }
+    ::timeline::home::fixup_home(timeline, limit);
+}
+
+// unread_label returns the label of side pane with the number of unread rows
+pub fn unread_label(name: &str, timeline: &Vec<TimelineRow>) -> String {
+    match unread_count(timeline) {
+        0 => name.to_owned(),
+        count => format!("{} ({})", name, count),
+    }
+}
+
+// escape_markup escapes text put in pango markup
+pub fn escape_markup(text: &str) -> String {
+    text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Local;
+
+    fn rule(pattern: &str, regex: bool) -> ::config::MuteRule {
+        ::config::MuteRule {
+            pattern: pattern.to_owned(),
+            regex: regex,
+            expires_at: None,
+            retweets_only: false,
+            non_followed_only: false,
+        }
+    }
+
+    fn user(screen_name: &str, following: Option<bool>) -> User {
+        User {
+            screen_name: screen_name.to_owned(),
+            profile_image_url: String::new(),
+            id: None,
+            following: following,
+        }
+    }
+
+    fn tweet(text: &str, following: Option<bool>) -> Tweet {
+        Tweet {
+            created_at: String::new(),
+            id: 1,
+            text: text.to_owned(),
+            attr: String::new(),
+            user: user("author", following),
+            in_reply_to_status_id: None,
+            quoted_status: None,
+            retweeted_status: None,
+            engagement: None,
+        }
+    }
+
+    #[test]
+    fn mute_regex_matches_whole_words() {
+        let re = mute_regex(&rule("cat", false)).unwrap();
+        assert!(re.is_match("a CAT sat"));
+        assert!(re.is_match("cat."));
+        assert!(!re.is_match("concatenate"));
+        let re = mute_regex(&rule("#tag", false)).unwrap();
+        assert!(re.is_match("see #Tag now"));
+        assert!(!re.is_match("see #tagged"));
+    }
+
+    #[test]
+    fn mute_regex_quotes_phrase_and_keeps_regex() {
+        assert!(!mute_regex(&rule("a.c", false)).unwrap().is_match("abc"));
+        assert!(mute_regex(&rule("a.c", true)).unwrap().is_match("xabcx"));
+        assert!(mute_regex(&rule("(", true)).is_err());
+    }
+
+    #[test]
+    fn mute_reason_filters_by_rule() {
+        set_mute_rules(vec![
+            ::config::MuteRule {
+                non_followed_only: true,
+                ..rule("spoiler", false)
+            },
+        ]);
+        assert!(mute_reason(&tweet("no spoiler here", None)).is_some());
+        assert!(mute_reason(&tweet("no spoiler here", Some(false))).is_some());
+        assert!(mute_reason(&tweet("no spoiler here", Some(true))).is_none());
+        assert!(mute_reason(&tweet("nothing", None)).is_none());
+
+        set_mute_rules(vec![
+            ::config::MuteRule {
+                retweets_only: true,
+                ..rule("spoiler", false)
+            },
+        ]);
+        assert!(mute_reason(&tweet("spoiler", None)).is_none());
+        let retweet = Tweet {
+            retweeted_status: Some(::timeline::home::RetweetedStatus {
+                id: 2,
+                text: "spoiler".to_owned(),
+                user: user("original", None),
+                created_at: String::new(),
+                in_reply_to_status_id: None,
+            }),
+            ..tweet("RT @original: spoiler", Some(true))
+        };
+        assert!(mute_reason(&retweet).is_some());
+    }
+
+    #[test]
+    fn mute_reason_skips_expired_rules() {
+        let now = Local::now().timestamp();
+        let expired = ::config::MuteRule {
+            expires_at: Some(now - 1),
+            ..rule("spoiler", false)
+        };
+        let active = ::config::MuteRule {
+            expires_at: Some(now + 3600),
+            ..rule("spoiler", false)
+        };
+        assert!(expired.is_expired());
+        assert!(!active.is_expired());
+        set_mute_rules(vec![expired]);
+        assert!(mute_reason(&tweet("spoiler", None)).is_none());
+        set_mute_rules(vec![active]);
+        assert!(mute_reason(&tweet("spoiler", None)).is_some());
+    }
+}
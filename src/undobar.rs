@@ -0,0 +1,107 @@
+use glib;
+use gtk;
+use gtk::prelude::*;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+// RESPONSE_UNDO is response id of undo button
+pub const RESPONSE_UNDO: i32 = 2;
+
+// UNDO_SECONDS is the grace period before a deferred request is sent
+pub const UNDO_SECONDS: u32 = 5;
+
+// Pending is a deferred request, done is set when it is sent or undone
+struct Pending {
+    done: Rc<Cell<bool>>,
+    commit: Rc<Fn()>,
+    undo: Rc<Fn()>,
+}
+
+// UndoBar defers requests like deleting a tweet for a grace period, the latest one is cancelled by undo button
+#[derive(Clone)]
+pub struct UndoBar {
+    pub infobar: gtk::InfoBar,
+    label: gtk::Label,
+    pending: Rc<RefCell<Vec<Pending>>>,
+}
+
+impl UndoBar {
+    pub fn new() -> UndoBar {
+        let infobar = gtk::InfoBar::new();
+        infobar.set_message_type(gtk::MessageType::Info);
+
+        let label = gtk::Label::new(None);
+        label.set_xalign(0.0);
+        if let Some(content_area) = infobar.get_content_area() {
+            content_area.add(&label);
+        }
+        infobar.add_button("Undo", RESPONSE_UNDO);
+
+        let bar = UndoBar {
+            infobar: infobar,
+            label: label,
+            pending: Rc::new(RefCell::new(Vec::new())),
+        };
+
+        // event definition, when undo button is clicked
+        {
+            let bar = bar.clone();
+
+            bar.infobar.clone().connect_response(move |_, response| if response == RESPONSE_UNDO {
+                let pending = bar.pending.borrow_mut().pop();
+                if let Some(pending) = pending {
+                    pending.done.set(true);
+                    (pending.undo)();
+                }
+                bar.update();
+            });
+        }
+        bar
+    }
+
+    // defer shows message and calls commit after UNDO_SECONDS, undo is called instead when undo button is clicked
+    pub fn defer<C: Fn() + 'static, U: Fn() + 'static>(&self, message: &str, commit: C, undo: U) {
+        let done = Rc::new(Cell::new(false));
+        let commit: Rc<Fn()> = Rc::new(commit);
+        self.pending.borrow_mut().push(Pending {
+            done: done.clone(),
+            commit: commit.clone(),
+            undo: Rc::new(undo),
+        });
+        self.label.set_text(message);
+        self.infobar.show();
+        self.label.show();
+
+        let bar = self.clone();
+        gtk::timeout_add_seconds(UNDO_SECONDS, move || {
+            if !done.get() {
+                done.set(true);
+                bar.pending.borrow_mut().retain(|pending| !pending.done.get());
+                bar.update();
+                commit();
+            }
+            glib::Continue(false)
+        });
+    }
+
+    // flush sends all deferred requests at once, it is called before exit
+    pub fn flush(&self) {
+        let pending: Vec<Pending> = self.pending.borrow_mut().drain(..).collect();
+        for pending in pending {
+            pending.done.set(true);
+            (pending.commit)();
+        }
+        self.update();
+    }
+
+    pub fn hide(&self) {
+        self.infobar.hide();
+    }
+
+    // update hides the bar when no request is deferred
+    fn update(&self) {
+        if self.pending.borrow().is_empty() {
+            self.infobar.hide();
+        }
+    }
+}
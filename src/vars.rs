@@ -8,11 +8,32 @@ pub const CONFIG: &'static str = "config.toml";
 
 // CACHE_HOME is cache of home
 pub const CACHE_HOME: &'static str = "home_timeline.json";
+// CACHE_MENTIONS is cache of mentions
+pub const CACHE_MENTIONS: &'static str = "mentions_timeline.json";
+// CACHE_LIKES is cache of likes
+pub const CACHE_LIKES: &'static str = "likes_timeline.json";
+// CACHE_DIRECT_MESSAGES is cache of direct message conversations
+pub const CACHE_DIRECT_MESSAGES: &'static str = "direct_messages.json";
+// CACHE_OUTBOX is cache of queued and scheduled tweets
+pub const CACHE_OUTBOX: &'static str = "outbox.json";
+// CACHE_LIST is prefix of cache of each list, the list id follows it
+pub const CACHE_LIST: &'static str = "list_";
+// CACHE_SEARCH is prefix of cache of each saved search, the digest of the query follows it
+pub const CACHE_SEARCH: &'static str = "search_";
+
+// CACHE_MEDIA_DIR is directory of pasted images under the cache directory, queued tweets refer to them
+pub const CACHE_MEDIA_DIR: &'static str = "media";
 
 // CONSUMER_KEY is twitter consumer key
 pub const CONSUMER_KEY: &'static str = "ojbRjiIFJ75K2AoDnW1WycfeI";
 // CONSUMER_KEY_SECRET is twitter consumer key secret
 pub const CONSUMER_KEY_SECRET: &'static str = "oWGO7DIr16hsLGGDEvQZhPb0zAtrJmGE43IfIUMges3h91ZiRH";
 
+// PAGE_SIZE is the maximum count of a timeline request, twitter returns no more tweets per page
+pub const PAGE_SIZE: usize = 200;
+
 // UNREAD_IMAGE_SIZE is unread image pixel size in timeline
 pub const UNREAD_IMAGE_SIZE: i32 = 8;
+
+// SECRETS is encrypted credential store under the config directory
+pub const SECRETS: &'static str = "secrets.json";
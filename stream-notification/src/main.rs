@@ -1,33 +1,76 @@
 #[macro_use]
 extern crate hyper;
+#[macro_use]
+extern crate log;
 extern crate egg_mode;
+extern crate env_logger;
 extern crate rustc_serialize;
+extern crate toml;
 extern crate url;
 extern crate notify_rust;
 
 mod tweet;
 mod notification;
+#[path = "../../src/mute.rs"]
+mod mute;
 
 use egg_mode::KeyPair;
-use egg_mode::auth::{self, Token};
+use egg_mode::auth;
 use egg_mode::common::*;
-use egg_mode::user;
 use hyper::Client;
 use hyper::header::{Authorization, ContentType, Headers};
 use hyper::method::Method;
 use hyper::mime::Mime;
 
+use rustc_serialize::json;
 use std::collections::HashMap;
-use std::io::{BufRead, BufReader};
+use std::env;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
 use std::str;
+use std::thread;
+use std::time::{Duration, SystemTime};
 use url::percent_encoding::{EncodeSet, utf8_percent_encode};
 
-// Change these values to your real Twitter API credentials
+// Change these values to your real Twitter API credentials, or override them by environment variables
 const CONSUMER: &'static str = "xxxxxxxxxxxxxxxxxxxxxxxxx";
 const CONSUMER_SECRET: &'static str = "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx";
 const TOKEN: &'static str = "000000000-xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx";
 const TOKEN_SECRET: &'static str = "xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx";
-const ID: &'static str = "twitter"
+const ID: &'static str = "twitter";
+
+// Base urls of twitter API, override them to point to a mock server
+const REST_BASE_URL: &'static str = "https://api.twitter.com/1.1";
+const STREAM_BASE_URL: &'static str = "https://stream.twitter.com/1.1";
+
+// RETRY_SECS is the wait before requests are retried after an error or the end of the stream
+const RETRY_SECS: u64 = 60;
+
+// NotifyError
+#[derive(Debug)]
+enum NotifyError {
+    Hyper(hyper::Error),
+    Json(json::ParserError),
+    Status(hyper::status::StatusCode),
+}
+
+impl From<hyper::Error> for NotifyError {
+    fn from(err: hyper::Error) -> NotifyError {
+        NotifyError::Hyper(err)
+    }
+}
+
+impl From<json::ParserError> for NotifyError {
+    fn from(err: json::ParserError) -> NotifyError {
+        NotifyError::Json(err)
+    }
+}
+
+// env_or returns the value of environment variable name, or default when it is not set
+fn env_or(name: &str, default: &str) -> String {
+    env::var(name).unwrap_or(default.to_owned()).trim_right_matches('/').to_owned()
+}
 
 // Struct TwitterEncodeSet
 #[derive(Copy, Clone)]
@@ -43,18 +86,55 @@ impl EncodeSet for TwitterEncodeSet {
 }
 
 fn main() {
-    let consumer_token = KeyPair::new(CONSUMER, CONSUMER_SECRET);
-    let access_token = KeyPair::new(TOKEN, TOKEN_SECRET);
-    let token = Token::Access {
-        consumer: consumer_token.clone(),
-        access: access_token.clone(),
-    };
+    env_logger::init().unwrap();
+    let consumer_token = KeyPair::new(env_or("RUSTYTWIT_CONSUMER_KEY", CONSUMER),
+                                      env_or("RUSTYTWIT_CONSUMER_SECRET", CONSUMER_SECRET));
+    let access_token = KeyPair::new(env_or("RUSTYTWIT_ACCESS_KEY", TOKEN),
+                                    env_or("RUSTYTWIT_ACCESS_SECRET", TOKEN_SECRET));
+    let rest_base_url = env_or("RUSTYTWIT_REST_BASE_URL", REST_BASE_URL);
+    let stream_base_url = env_or("RUSTYTWIT_STREAM_BASE_URL", STREAM_BASE_URL);
+    let screen_name = env_or("RUSTYTWIT_SCREEN_NAME", ID);
+    // Muted users are read from muted in config.toml of rustytwit, and reloaded when rustytwit writes it
+    let mut muted = MuteList::new(config_path());
 
-    let api = user::friends_ids(ID, &token);
-    let friends_ids = api.call().unwrap();
-    println!("number of follows: {:?}", friends_ids.ids.len());
+    // Errors of the API and the end of the stream are logged, and the stream is connected again
+    loop {
+        match notify(&consumer_token,
+                     &access_token,
+                     &rest_base_url,
+                     &stream_base_url,
+                     &screen_name,
+                     &mut muted) {
+            Ok(_) => info!("stream ended, reconnecting in {} seconds", RETRY_SECS),
+            Err(err) => error!("{:?}, retrying in {} seconds", err, RETRY_SECS),
+        }
+        thread::sleep(Duration::from_secs(RETRY_SECS));
+    }
+}
 
-    let follow = friends_ids.response
+// notify requests friends of screen_name and notifies their tweets on the stream until it ends
+fn notify(consumer_token: &KeyPair,
+          access_token: &KeyPair,
+          rest_base_url: &str,
+          stream_base_url: &str,
+          screen_name: &str,
+          muted: &mut MuteList)
+          -> Result<(), NotifyError> {
+    let mut params = HashMap::new();
+    add_param(&mut params, "screen_name", screen_name.to_owned());
+
+    let url = format!("{}/friends/ids.json", rest_base_url);
+    let resp = try!(signed_get(&url, &params, consumer_token, access_token));
+    let friends = try!(json::Json::from_reader(&mut BufReader::new(resp)));
+    let friends_ids = FriendsIds {
+        ids: friends.find("ids")
+            .and_then(|ids| ids.as_array())
+            .map(|ids| ids.iter().filter_map(|id| id.as_u64()).collect())
+            .unwrap_or(Vec::new()),
+    };
+    info!("number of follows: {:?}", friends_ids.ids.len());
+
+    let follow = friends_ids
         .ids
         .iter()
         .map(|f| format!("{}", f))
@@ -64,40 +144,8 @@ fn main() {
     let mut params = HashMap::new();
     add_param(&mut params, "follow", follow);
 
-    let url = "https://stream.twitter.com/1.1/statuses/filter.json";
-
-    // Construct full url
-    let full_url = match Some(&params) {
-        Some(params) => {
-            let query = params.iter()
-                .map(|(k, v)| {
-                    format!("{}={}",
-                            utf8_percent_encode(k, TwitterEncodeSet).collect::<String>(),
-                            utf8_percent_encode(v, TwitterEncodeSet).collect::<String>())
-                })
-                .collect::<Vec<_>>()
-                .join("&");
-            format!("{}?{}", url, query)
-        },
-        None => url.to_string(),
-    };
-
-    // Construct headers
-    let mut headers = Headers::new();
-    let header = auth::get_header(Method::Get,
-                                  url,
-                                  &consumer_token,
-                                  Some(&access_token),
-                                  None,
-                                  None,
-                                  Some(&params));
-    let content: Mime = "application/x-www-form-urlencoded".parse().unwrap();
-    headers.set(Authorization(header.to_owned()));
-    headers.set(ContentType(content));
-
-    // Api call
-    let client = Client::new();
-    let resp = client.get(&full_url).headers(headers).send().unwrap();
+    let url = format!("{}/statuses/filter.json", stream_base_url);
+    let resp = try!(signed_get(&url, &params, consumer_token, access_token));
 
     // Display and formatting tweet
     for resp_str in BufReader::new(resp).as_str() {
@@ -112,6 +160,13 @@ fn main() {
 
                 let tweet = web_resp.unwrap();
 
+                // Tweets of muted users and retweets of them are not notified
+                let muted = muted.reload();
+                if is_muted(muted, &tweet.user) ||
+                   tweet.retweeted_status.as_ref().map(|status| is_muted(muted, &status.user)).unwrap_or(false) {
+                    continue;
+                }
+
                 if let Some(ref retweeted_status) = tweet.retweeted_status {
                     // When the tweet is retweet and that is retweeted by friends
                     for id in &friends_ids.ids {
@@ -133,15 +188,134 @@ fn main() {
                         text: tweet.text.clone(),
                     };
                     notification::new(obj);
-                    break;
                 }
             },
             Err(_) => {
-                println!("resp_str: {}", resp_str);
+                debug!("resp_str: {}", resp_str);
                 // println!("tweet is deleted");
             },
         }
     }
+    Ok(())
+}
+
+// config_path returns config.toml of rustytwit, RUSTYTWIT_CONFIG overrides it
+fn config_path() -> PathBuf {
+    match env::var("RUSTYTWIT_CONFIG") {
+        Ok(path) => PathBuf::from(path),
+        Err(_) => {
+            env::home_dir()
+                .unwrap_or(PathBuf::from("."))
+                .join(".config")
+                .join("rustytwit")
+                .join("config.toml")
+        },
+    }
+}
+
+// MuteList is muted of config.toml, it is loaded again when the modified time of the file changes
+struct MuteList {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    muted: Vec<String>,
+}
+
+impl MuteList {
+    fn new(path: PathBuf) -> MuteList {
+        MuteList {
+            path: path,
+            modified: None,
+            muted: Vec::new(),
+        }
+    }
+
+    // reload loads the mute list when config.toml is modified, the last list is kept when it is not readable
+    fn reload(&mut self) -> &Vec<String> {
+        let modified = fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+        if modified.is_some() && modified != self.modified {
+            let mut body = String::new();
+            match File::open(&self.path).and_then(|mut file| file.read_to_string(&mut body)) {
+                Ok(_) => {
+                    match body.parse::<toml::Value>() {
+                        Ok(value) => {
+                            self.muted = muted_users(&value);
+                            self.modified = modified;
+                            info!("number of muted users: {}", self.muted.len());
+                        },
+                        Err(err) => error!("failed to parse {:?}: {:?}", self.path, err),
+                    }
+                },
+                Err(err) => error!("failed to read {:?}: {:?}", self.path, err),
+            }
+        }
+        &self.muted
+    }
+}
+
+// muted_users returns the top-level muted array of config, an entry is a screen name or a prefixed user id
+fn muted_users(config: &toml::Value) -> Vec<String> {
+    config.lookup("muted")
+          .and_then(|muted| muted.as_slice())
+          .unwrap_or(&[])
+          .iter()
+          .filter_map(|entry| entry.as_str())
+          .map(|entry| mute::normalize(entry))
+          .filter(|entry| !entry.is_empty())
+          .collect()
+}
+
+// is_muted returns true when user id or screen name of user is in the mute list
+fn is_muted(muted: &Vec<String>, user: &tweet::TwitterUser) -> bool {
+    muted.iter().any(|entry| mute::is_muted_user(entry, Some(user.id as u64), &user.screen_name))
+}
+
+struct FriendsIds {
+    ids: Vec<u64>,
+}
+
+// signed_get sends OAuth signed GET request to url
+fn signed_get(url: &str,
+              params: &HashMap<&'static str, String>,
+              consumer_token: &KeyPair,
+              access_token: &KeyPair)
+              -> Result<hyper::client::Response, NotifyError> {
+    // Construct full url
+    let full_url = match Some(params) {
+        Some(params) => {
+            let query = params.iter()
+                .map(|(k, v)| {
+                    format!("{}={}",
+                            utf8_percent_encode(k, TwitterEncodeSet).collect::<String>(),
+                            utf8_percent_encode(v, TwitterEncodeSet).collect::<String>())
+                })
+                .collect::<Vec<_>>()
+                .join("&");
+            format!("{}?{}", url, query)
+        },
+        None => url.to_string(),
+    };
+
+    // Construct headers
+    let mut headers = Headers::new();
+    let header = auth::get_header(Method::Get,
+                                  url,
+                                  consumer_token,
+                                  Some(access_token),
+                                  None,
+                                  None,
+                                  Some(params));
+    let content: Mime = "application/x-www-form-urlencoded".parse().unwrap();
+    headers.set(Authorization(header.to_owned()));
+    headers.set(ContentType(content));
+
+    // Api call
+    let client = Client::new();
+    let resp = try!(client.get(&full_url).headers(headers).send());
+    if !resp.status.is_success() {
+        return Err(NotifyError::Status(resp.status));
+    }
+    Ok(resp)
+
 }
 
 pub struct JsonStr<'a, B>
@@ -167,9 +341,12 @@ impl<'a, B> Iterator for JsonStr<'a, B> where B: BufRead + 'a
     fn next(&mut self) -> Option<String> {
         let mut buf: Vec<u8> = Vec::new();
 
-        // Waiting to read endswith '\b'
+        // Waiting to read endswith '\b', the stream ends when the connection is closed
         loop {
-            let _ = self.reader.read_until(10, &mut buf);
+            match self.reader.read_until(10, &mut buf) {
+                Ok(0) | Err(_) => return None,
+                Ok(_) => (),
+            }
             if buf.len() == 2 {
                 buf.clear();
                 continue;
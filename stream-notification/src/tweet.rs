@@ -170,8 +170,9 @@ impl FromJson for Tweet {
             return Err(InvalidResponse("Tweet received json that wasn't an object", Some(input.to_string())));
         }
 
-        //TODO: when i start building streams, i want to extract "extended_tweet" and use its
-        //fields here
+        //streams put the text, entities and display range of tweets longer than 140 characters in
+        //"extended_tweet", the other fields are in the tweet itself
+        let extended = input.find("extended_tweet").unwrap_or(input);
 
         let coords = field(input, "coordinates").ok();
 
@@ -180,9 +181,9 @@ impl FromJson for Tweet {
             coordinates: coords.map(|(lon, lat)| (lat, lon)),
             created_at: try!(field(input, "created_at")),
             current_user_retweet: try!(current_user_retweet(input, "current_user_retweet")),
-            display_text_range: field(input, "display_text_range").ok(),
-            entities: try!(field(input, "entities")),
-            extended_entities: field(input, "extended_entities").ok(),
+            display_text_range: field(extended, "display_text_range").ok(),
+            entities: try!(field(extended, "entities")),
+            extended_entities: field(extended, "extended_entities").ok(),
             favorite_count: field(input, "favorite_count").unwrap_or(0),
             favorited: field(input, "favorited").ok(),
             //filter_level: FilterLevel,
@@ -200,7 +201,7 @@ impl FromJson for Tweet {
             retweeted: field(input, "retweeted").ok(),
             retweeted_status: field(input, "retweeted_status").map(Box::new).ok(),
             source: try!(field(input, "source")),
-            text: try!(field(input, "full_text").or(field(input, "text"))),
+            text: try!(field(extended, "full_text").or(field(input, "text"))),
             truncated: try!(field(input, "truncated")),
             user: try!(field(input, "user").map(Box::new)),
             withheld_copyright: field(input, "withheld_copyright").unwrap_or(false),